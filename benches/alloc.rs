@@ -0,0 +1,55 @@
+//! Tight allocation loops, run with `cargo bench`.
+#![feature(allocator_api, test)]
+
+extern crate test;
+
+use arena::Arena;
+use test::{black_box, Bencher};
+
+const N: usize = 1024;
+
+#[bench]
+fn alloc_u64(b: &mut Bencher) {
+    let mut arena = Arena::with_capacity(N * 8).unwrap();
+    b.iter(|| {
+        for i in 0..N as u64 {
+            black_box(arena.alloc(i).unwrap());
+        }
+        arena.reset();
+    });
+}
+
+#[bench]
+fn alloc_bytes(b: &mut Bencher) {
+    let mut arena = Arena::with_capacity(N * 4).unwrap();
+    b.iter(|| {
+        for i in 0..N {
+            black_box(arena.alloc(i as u8).unwrap());
+            black_box(arena.alloc_str("abc").unwrap());
+        }
+        arena.reset();
+    });
+}
+
+#[bench]
+fn alloc_mixed_alignment(b: &mut Bencher) {
+    let mut arena = Arena::with_capacity(N * 16).unwrap();
+    b.iter(|| {
+        for i in 0..N / 2 {
+            black_box(arena.alloc(i as u8).unwrap());
+            black_box(arena.alloc(i as u64).unwrap());
+        }
+        arena.reset();
+    });
+}
+
+#[bench]
+fn box_new_in(b: &mut Bencher) {
+    let mut arena = Arena::with_capacity(N * 4).unwrap();
+    b.iter(|| {
+        for i in 0..N as u32 {
+            black_box(Box::new_in(i, &arena));
+        }
+        arena.reset();
+    });
+}
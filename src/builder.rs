@@ -0,0 +1,120 @@
+use alloc::alloc::Global;
+use core::alloc::AllocError;
+
+use crate::{Arena, Storage};
+
+/// Configures an [`Arena`] whose options do not fit one of the
+/// `with_capacity*` constructors, e.g. an aligned and zeroed buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaBuilder {
+    capacity: usize,
+    base_align: usize,
+    zeroed: bool,
+    granularity: usize,
+}
+
+impl ArenaBuilder {
+    /// An empty, byte-aligned, uninitialized arena until configured.
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            base_align: 1,
+            zeroed: false,
+            granularity: 1,
+        }
+    }
+
+    /// Size of the backing buffer in bytes.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Alignment of the start of the buffer; see
+    /// [`Arena::with_capacity_aligned`]. Must be a power of two.
+    pub fn base_align(mut self, align: usize) -> Self {
+        self.base_align = align;
+        self
+    }
+
+    /// Whether to zero the buffer up front; see
+    /// [`Arena::with_capacity_zeroed`].
+    pub fn zeroed(mut self, zeroed: bool) -> Self {
+        self.zeroed = zeroed;
+        self
+    }
+
+    /// Starts every block on a multiple of `granularity` bytes, trading
+    /// space for never padding objects aligned to at most that. Must be a
+    /// power of two.
+    pub fn granularity(mut self, granularity: usize) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    pub fn build(self) -> Result<Arena<'static>, AllocError> {
+        if !self.granularity.is_power_of_two() {
+            return Err(AllocError);
+        }
+        let storage = Storage::allocate(self.capacity, self.base_align, &Global, self.zeroed)?;
+        let mut arena = Arena::from_storage(storage);
+        arena.granularity = self.granularity;
+        if self.zeroed {
+            arena.zeroed.set((0, self.capacity));
+        }
+        Ok(arena)
+    }
+}
+
+impl Default for ArenaBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Arena;
+
+    #[test]
+    fn test_aligned_zeroed() {
+        let arena = Arena::builder()
+            .capacity(1024)
+            .base_align(64)
+            .zeroed(true)
+            .build()
+            .unwrap();
+        assert_eq!(arena.capacity(), 1024);
+        assert_eq!(arena.allocation.as_ptr() as usize % 64, 0);
+        assert_eq!(
+            arena.padding_for(core::alloc::Layout::new::<[u8; 64]>()),
+            Some(0)
+        );
+        assert!(unsafe { arena.into_boxed_slice() }.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let arena = Arena::builder().build().unwrap();
+        assert_eq!(arena.capacity(), 0);
+        assert!(Arena::builder().base_align(3).capacity(8).build().is_err());
+    }
+
+    #[test]
+    fn test_granularity() {
+        let arena = Arena::builder()
+            .capacity(256)
+            .base_align(16)
+            .granularity(16)
+            .build()
+            .unwrap();
+        for i in 0..8u8 {
+            let byte = arena.alloc(i).unwrap();
+            assert_eq!(byte as *mut u8 as usize % 16, 0);
+        }
+        assert_eq!(arena.used(), 7 * 16 + 1);
+        let squares = arena.alloc_from_iter((0..4u8).map(|i| i * i)).unwrap();
+        assert_eq!(squares, [0, 1, 4, 9]);
+        assert!(Arena::builder().granularity(3).build().is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use core::{fmt, ptr::NonNull};
+
+use crate::{Arena, ArenaError};
+
+/// A byte buffer that grows in place at the end of an [`Arena`], so appending
+/// never copies and never touches the global heap.
+///
+/// It can only grow while it is the arena's last allocation; once something
+/// else is allocated after it, appending fails with
+/// [`ArenaError::NotLastBlock`].
+#[derive(Debug)]
+pub struct ArenaBytes<'a> {
+    arena: &'a Arena<'a>,
+    start: NonNull<u8>,
+    len: usize,
+}
+
+impl<'a> ArenaBytes<'a> {
+    /// An empty buffer; nothing is allocated until the first byte is added.
+    pub fn new(arena: &'a Arena<'a>) -> Self {
+        Self {
+            arena,
+            start: NonNull::dangling(),
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Result<(), ArenaError> {
+        self.extend_from_slice(&[byte])
+    }
+
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), ArenaError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        // `start` comes straight from the arena rather than from a reference
+        // to the first bytes, so it stays valid for the bytes grown into.
+        if self.len == 0 {
+            let layout = Arena::array_layout::<u8>(bytes.len())?;
+            self.start = self.arena.bump(layout)?.cast();
+        } else {
+            let layout = Arena::array_layout::<u8>(self.len + bytes.len())?;
+            self.arena
+                .grow_last(self.start.as_ptr(), self.len, layout)?;
+        }
+        unsafe {
+            self.start
+                .as_ptr()
+                .add(self.len)
+                .copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+        }
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.start.as_ptr(), self.len) }
+    }
+
+    /// Gives up the ability to append and returns the bytes for as long as
+    /// the arena is borrowed.
+    pub fn into_slice(self) -> &'a [u8] {
+        unsafe { core::slice::from_raw_parts(self.start.as_ptr(), self.len) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// A [`fmt::Write`] sink that formats straight into an [`Arena`], growing
+/// in place like [`ArenaBytes`], so no intermediate `String` is needed.
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// let arena = arena::Arena::with_capacity(64).unwrap();
+/// let mut writer = arena.writer();
+/// write!(writer, "{} + {}", 1, 2).unwrap();
+/// assert_eq!(writer.finish(), "1 + 2");
+/// ```
+#[derive(Debug)]
+pub struct ArenaWriter<'a> {
+    bytes: ArenaBytes<'a>,
+}
+
+impl<'a> ArenaWriter<'a> {
+    pub fn new(arena: &'a Arena<'a>) -> Self {
+        Self {
+            bytes: ArenaBytes::new(arena),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY: only whole `&str`s are ever appended.
+        unsafe { core::str::from_utf8_unchecked(self.bytes.as_slice()) }
+    }
+
+    /// Returns the formatted string for as long as the arena is borrowed.
+    pub fn finish(self) -> &'a str {
+        unsafe { core::str::from_utf8_unchecked(self.bytes.into_slice()) }
+    }
+}
+
+impl fmt::Write for ArenaWriter<'_> {
+    /// Fails if the arena is full or something else was allocated since the
+    /// last write.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.bytes
+            .extend_from_slice(s.as_bytes())
+            .map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use crate::{Arena, ArenaBytes, ArenaError, ArenaWriter};
+
+    #[test]
+    fn it_works() {
+        let arena = Arena::with_capacity_aligned(16, 4).unwrap();
+        arena.alloc(0u32).unwrap();
+        let mut bytes = ArenaBytes::new(&arena);
+        bytes.extend_from_slice(b"hello").unwrap();
+        let start = bytes.as_slice().as_ptr();
+        bytes.push(b' ').unwrap();
+        bytes.extend_from_slice(b"you").unwrap();
+        assert_eq!(bytes.as_slice(), b"hello you");
+        assert_eq!(bytes.as_slice().as_ptr(), start);
+        assert_eq!(arena.used(), 4 + 9);
+        assert!(matches!(
+            bytes.extend_from_slice(b"!!!!"),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+        assert_eq!(bytes.len(), 9);
+    }
+
+    #[test]
+    fn test_interleaved_allocation_fails() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let mut bytes = ArenaBytes::new(&arena);
+        bytes.push(1).unwrap();
+        arena.alloc(2u8).unwrap();
+        assert_eq!(bytes.push(3), Err(ArenaError::NotLastBlock));
+        assert_eq!(bytes.as_slice(), [1]);
+    }
+
+    #[test]
+    fn test_writer() {
+        let arena = Arena::with_capacity(32).unwrap();
+        let mut writer = arena.writer();
+        write!(writer, "{:>5}|{:.2}", 42, 1.5).unwrap();
+        assert_eq!(writer.as_str(), "   42|1.50");
+        let s = writer.finish();
+        assert_eq!(s, "   42|1.50");
+        assert_eq!(arena.used(), s.len());
+
+        let mut full = ArenaWriter::new(&arena);
+        assert!(write!(full, "{}", "x".repeat(64)).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::{vec, vec::Vec};
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::RefCell,
+    ptr::NonNull,
+};
+
+use crate::Arena;
+
+/// An arena that never runs out while the global allocator has memory: when
+/// the current chunk is full it starts a new one twice as large. Chunks are
+/// never moved or freed before the arena is dropped, so earlier allocations
+/// stay valid, unlike with [`Arena::grow_to`].
+#[derive(Debug)]
+pub struct ChunkedArena {
+    chunks: RefCell<Vec<Arena<'static>>>,
+}
+
+impl ChunkedArena {
+    /// Creates an arena whose first chunk holds `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        Ok(Self {
+            chunks: RefCell::new(vec![Arena::with_capacity(capacity)?]),
+        })
+    }
+
+    /// Number of chunks allocated so far.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.borrow().len()
+    }
+
+    /// Total size of all chunks in bytes.
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(Arena::capacity).sum()
+    }
+
+    /// Bytes handed out across all chunks, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.chunks.borrow().iter().map(Arena::used).sum()
+    }
+}
+
+unsafe impl Allocator for &ChunkedArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks
+            .last()
+            .expect("a chunked arena has at least one chunk");
+        if let Ok(ptr) = last.allocate(layout) {
+            return Ok(ptr);
+        }
+        // Reserving `align - 1` extra bytes guarantees the block fits however
+        // the new chunk happens to be aligned.
+        let needed = layout
+            .size()
+            .checked_add(layout.align() - 1)
+            .ok_or(AllocError)?;
+        let capacity = last.capacity().saturating_mul(2).max(needed);
+        chunks.push(Arena::with_capacity(capacity)?);
+        chunks.last().unwrap().allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // The owning chunk keeps its count of live blocks; only the newest
+        // can still reclaim its last block. Zero-sized blocks and foreign
+        // pointers belong to no chunk.
+        let chunks = self.chunks.borrow();
+        let Some(chunk) = chunks.iter().rev().find(|chunk| chunk.owns(ptr.as_ptr())) else {
+            return;
+        };
+        unsafe { chunk.deallocate(ptr, layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChunkedArena;
+
+    #[test]
+    fn test_allocations_survive_new_chunks() {
+        let arena = ChunkedArena::with_capacity(64).unwrap();
+        let boxes: Vec<_> = (0..5000u64).map(|i| Box::new_in(i, &arena)).collect();
+        assert!(arena.chunk_count() > 1);
+        assert!(arena.used() >= 5000 * 8);
+        for (i, b) in boxes.iter().enumerate() {
+            assert_eq!(**b, i as u64);
+        }
+    }
+
+    #[test]
+    fn test_free_in_older_chunk() {
+        let arena = ChunkedArena::with_capacity(16).unwrap();
+        let old = Box::new_in(1u64, &arena);
+        let new = Box::new_in([2u64; 4], &arena);
+        assert_eq!(arena.chunk_count(), 2);
+        drop(old);
+        let chunks = arena.chunks.borrow();
+        assert_eq!((chunks[0].live.get(), chunks[1].live.get()), (0, 1));
+        drop(chunks);
+        drop(new);
+        assert_eq!(arena.chunks.borrow()[1].live.get(), 0);
+    }
+
+    #[test]
+    fn test_oversized_allocation() {
+        let arena = ChunkedArena::with_capacity(0).unwrap();
+        let big = Box::new_in([3u128; 64], &arena);
+        assert_eq!(big[63], 3);
+        assert_eq!(big.as_ptr() as usize % 16, 0);
+        assert_eq!(arena.chunk_count(), 2);
+    }
+}
@@ -0,0 +1,62 @@
+use alloc::vec::Vec;
+use core::alloc::{Allocator, Layout};
+
+use crate::{Arena, ArenaError};
+
+/// Collects an iterator into a `Vec` backed by an [`Arena`], failing with an
+/// error where `collect` would abort.
+pub trait ArenaCollect: Iterator + Sized {
+    /// Reserves the lower bound of the size hint up front, then pushes the
+    /// rest. When the arena runs out, the items collected so far are dropped
+    /// and the error is returned.
+    fn collect_vec_in<'b, 'a, A: Allocator>(
+        self,
+        arena: &'b Arena<'a, A>,
+    ) -> Result<Vec<Self::Item, &'b Arena<'a, A>>, ArenaError> {
+        let mut vec = arena.alloc_vec(self.size_hint().0)?;
+        for item in self {
+            if vec.len() == vec.capacity()
+                && vec.try_reserve(1).is_err()
+                && vec.try_reserve_exact(1).is_err()
+            {
+                return Err(arena.fit_error(Layout::new::<Self::Item>()));
+            }
+            vec.push(item);
+        }
+        Ok(vec)
+    }
+}
+
+impl<I: Iterator> ArenaCollect for I {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, ArenaCollect, ArenaError};
+
+    #[test]
+    fn it_works() {
+        let arena = Arena::with_capacity_aligned(512, 4).unwrap();
+        let v = (0..100u32).collect_vec_in(&arena).unwrap();
+        assert_eq!(v.len(), 100);
+        assert_eq!(v.iter().sum::<u32>(), 4950);
+        assert_eq!(arena.used(), 400);
+
+        // No size hint: grown by pushing, in place while it is the last block.
+        let odd = (0..40u32).filter(|i| i % 2 == 1).collect_vec_in(&arena);
+        assert_eq!(odd.unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_too_small() {
+        let arena = Arena::with_capacity_aligned(16, 4).unwrap();
+        assert!(matches!(
+            (0..100u32).collect_vec_in(&arena),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+        let unhinted = (0..100u32).filter(|_| true).collect_vec_in(&arena);
+        assert!(matches!(
+            unhinted,
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+    }
+}
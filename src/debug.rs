@@ -0,0 +1,181 @@
+use alloc::collections::BTreeMap;
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::RefCell,
+    ops::Deref,
+    ptr::NonNull,
+};
+
+use crate::Arena;
+
+/// An [`Arena`] that checks the allocator contract. It records the layout
+/// of every block handed out through [`Allocator`] and panics when a block
+/// is deallocated, grown or shrunk with a different layout, or was never
+/// allocated from it. [`Arena`] itself ignores the layout in `deallocate`,
+/// so such bugs in collection code would otherwise go unnoticed.
+///
+/// Zero-sized blocks all share an address and own no memory, so they are
+/// not checked.
+#[derive(Debug)]
+pub struct DebugArena<'a> {
+    arena: Arena<'a>,
+    blocks: RefCell<BTreeMap<usize, Layout>>,
+}
+
+impl<'a> DebugArena<'a> {
+    pub fn new(arena: Arena<'a>) -> Self {
+        Self {
+            arena,
+            blocks: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn into_inner(self) -> Arena<'a> {
+        self.arena
+    }
+
+    /// Number of blocks allocated and not yet deallocated.
+    pub fn live_blocks(&self) -> usize {
+        self.blocks.borrow().len()
+    }
+
+    fn record(&self, block: NonNull<[u8]>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+        self.blocks
+            .borrow_mut()
+            .insert(block.cast::<u8>().as_ptr() as usize, layout);
+    }
+
+    /// Removes the record for `ptr`, panicking unless it was allocated with
+    /// `layout`.
+    #[track_caller]
+    fn check(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+        match self.blocks.borrow_mut().remove(&(ptr.as_ptr() as usize)) {
+            Some(recorded) => assert_eq!(
+                recorded, layout,
+                "block {ptr:p} was allocated with a different layout"
+            ),
+            None => panic!("block {ptr:p} was not allocated from this arena"),
+        }
+    }
+}
+
+impl<'a> Deref for DebugArena<'a> {
+    type Target = Arena<'a>;
+
+    fn deref(&self) -> &Arena<'a> {
+        &self.arena
+    }
+}
+
+unsafe impl Allocator for &DebugArena<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let block = (&self.arena).allocate(layout)?;
+        self.record(block, layout);
+        Ok(block)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let block = (&self.arena).allocate_zeroed(layout)?;
+        self.record(block, layout);
+        Ok(block)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.check(ptr, layout);
+        unsafe { (&self.arena).deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.check(ptr, old_layout);
+        let result = unsafe { (&self.arena).grow(ptr, old_layout, new_layout) };
+        // On failure the old block is still live.
+        match result {
+            Ok(block) => self.record(block, new_layout),
+            Err(_) => self.record(NonNull::slice_from_raw_parts(ptr, 0), old_layout),
+        }
+        result
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.check(ptr, old_layout);
+        let result = unsafe { (&self.arena).shrink(ptr, old_layout, new_layout) };
+        match result {
+            Ok(block) => self.record(block, new_layout),
+            Err(_) => self.record(NonNull::slice_from_raw_parts(ptr, 0), old_layout),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{Allocator, Layout};
+
+    use crate::{Arena, DebugArena};
+
+    #[test]
+    fn test_tracks_collections() {
+        let arena = DebugArena::new(Arena::with_capacity(1024).unwrap());
+        let mut v = Vec::new_in(&arena);
+        v.extend(0..50u32);
+        v.shrink_to_fit();
+        assert_eq!(arena.live_blocks(), 1);
+        drop(v);
+        assert_eq!(arena.live_blocks(), 0);
+    }
+
+    #[test]
+    fn test_zero_sized_blocks() {
+        let arena = DebugArena::new(Arena::with_capacity(64).unwrap());
+        let layout = Layout::new::<()>();
+        let first = (&arena).allocate(layout).unwrap().cast();
+        let second = (&arena).allocate(layout).unwrap().cast();
+        assert_eq!(first, second);
+        assert_eq!(arena.live_blocks(), 0);
+        unsafe {
+            (&arena).deallocate(first, layout);
+            (&arena).deallocate(second, layout);
+        }
+        let mut v: Vec<u8, _> = Vec::new_in(&arena);
+        v.push(1);
+        v.clear();
+        v.shrink_to_fit();
+        assert_eq!(arena.live_blocks(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "different layout")]
+    fn test_wrong_layout_panics() {
+        let arena = DebugArena::new(Arena::with_capacity(64).unwrap());
+        let ptr = (&arena).allocate(Layout::new::<u64>()).unwrap();
+        unsafe { (&arena).deallocate(ptr.cast(), Layout::new::<u32>()) };
+    }
+
+    #[test]
+    #[should_panic(expected = "not allocated from this arena")]
+    fn test_double_free_panics() {
+        let arena = DebugArena::new(Arena::with_capacity(64).unwrap());
+        let layout = Layout::new::<u64>();
+        let ptr = (&arena).allocate(layout).unwrap().cast();
+        unsafe {
+            (&arena).deallocate(ptr, layout);
+            (&arena).deallocate(ptr, layout);
+        }
+    }
+}
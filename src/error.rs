@@ -0,0 +1,43 @@
+use core::{alloc::AllocError, fmt};
+
+/// Why an arena allocation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaError {
+    /// The block does not fit in the space left. `requested` includes the
+    /// alignment padding the block would have needed.
+    CapacityExhausted { requested: usize, remaining: usize },
+    /// The requested size cannot be represented, e.g. a slice length whose
+    /// byte size overflows.
+    LayoutOverflow,
+    /// The arena has no backing buffer at all.
+    ZeroCapacity,
+    /// A block could not grow in place because something else was allocated
+    /// after it.
+    NotLastBlock,
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::CapacityExhausted {
+                requested,
+                remaining,
+            } => write!(
+                f,
+                "arena out of space: needed {requested}, have {remaining}"
+            ),
+            ArenaError::LayoutOverflow => f.write_str("allocation size overflows"),
+            ArenaError::ZeroCapacity => f.write_str("arena has no capacity"),
+            ArenaError::NotLastBlock => f.write_str("block is no longer the last allocation"),
+        }
+    }
+}
+
+impl core::error::Error for ArenaError {}
+
+/// The `Allocator` trait can only report the unit [`AllocError`].
+impl From<ArenaError> for AllocError {
+    fn from(_: ArenaError) -> Self {
+        AllocError
+    }
+}
@@ -0,0 +1,99 @@
+use alloc::boxed::Box;
+use core::mem::MaybeUninit;
+
+/// The used bytes of an [`Arena`](crate::Arena) after the build phase,
+/// created by [`Arena::freeze`](crate::Arena::freeze). Nothing can be
+/// allocated any more, so unlike the arena it has no interior mutability and
+/// can be shared across threads.
+///
+/// Blocks that were reserved but never written stay uninitialized, so the
+/// bytes are only readable as `u8` under the conditions of
+/// [`Arena::used_bytes`](crate::Arena::used_bytes).
+#[derive(Debug, Clone)]
+pub struct FrozenArena {
+    bytes: Box<[MaybeUninit<u8>]>,
+}
+
+impl FrozenArena {
+    pub(crate) fn new(bytes: Box<[MaybeUninit<u8>]>) -> Self {
+        Self { bytes }
+    }
+
+    /// # Safety
+    ///
+    /// As for [`Arena::used_bytes`](crate::Arena::used_bytes): every block
+    /// allocated before freezing must have been fully initialized.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        unsafe { self.bytes.assume_init_ref() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Reads a copy of the `T` at `offset`, e.g. one from
+    /// [`Arena::offset_of`](crate::Arena::offset_of) taken before freezing,
+    /// or `None` if it would extend past the end. Freezing may drop the
+    /// buffer's alignment, so the read is unaligned.
+    ///
+    /// # Safety
+    ///
+    /// The bytes at `offset` must hold a valid `T`, e.g. because one was
+    /// allocated there.
+    pub unsafe fn get<T: Copy>(&self, offset: usize) -> Option<T> {
+        let end = offset.checked_add(core::mem::size_of::<T>())?;
+        let bytes = self.bytes.get(offset..end)?;
+        Some(unsafe { bytes.as_ptr().cast::<T>().read_unaligned() })
+    }
+
+    /// # Safety
+    ///
+    /// As for [`FrozenArena::as_bytes`].
+    pub unsafe fn into_boxed_slice(self) -> Box<[u8]> {
+        unsafe { self.bytes.assume_init() }
+    }
+
+    pub fn into_boxed_uninit(self) -> Box<[MaybeUninit<u8>]> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, FrozenArena};
+
+    #[test]
+    fn test_shared_across_threads() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc_str("head").unwrap();
+        let value = arena.alloc(0x1234_5678_u32).unwrap() as *const u32;
+        let offset = arena.offset_of(value.cast()).unwrap();
+        let frozen: FrozenArena = arena.freeze();
+        assert_eq!(unsafe { &frozen.as_bytes()[..4] }, b"head");
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    assert_eq!(unsafe { frozen.get::<u32>(offset) }, Some(0x1234_5678));
+                    assert_eq!(unsafe { frozen.get::<u32>(frozen.len() - 3) }, None);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_unwritten_block() {
+        // Freezing and cloning only move the bytes, so a block that was
+        // never written is fine as long as nobody reads it; Miri checks.
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc_uninit::<u64>().unwrap();
+        let value = arena.alloc(7u32).unwrap() as *const u32;
+        let offset = arena.offset_of(value.cast()).unwrap();
+        let frozen = arena.freeze().clone();
+        assert_eq!(frozen.len(), 12);
+        assert_eq!(unsafe { frozen.get::<u32>(offset) }, Some(7));
+    }
+}
@@ -0,0 +1,240 @@
+use alloc::alloc::handle_alloc_error;
+use core::{
+    alloc::{AllocError, Layout},
+    fmt,
+};
+
+use crate::{Arena, FitResult, NodeRef};
+
+/// An arena that doubles its buffer when full, like a `Vec`. Growing moves
+/// the buffer, so allocations are returned as offset-based [`Handle`]s
+/// instead of references, and allocating takes `&mut self` so no reference
+/// from [`GrowableArena::get`] can be alive across a move.
+#[derive(Debug)]
+pub struct GrowableArena {
+    arena: Arena<'static>,
+}
+
+/// A value in a [`GrowableArena`], valid across growth.
+pub struct Handle<T>(NodeRef<T>);
+
+// Like `NodeRef`, a handle is copyable and comparable whatever `T` is.
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.0.offset()).finish()
+    }
+}
+
+impl GrowableArena {
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        Ok(Self {
+            // Every buffer is aligned like this one, so offsets of values
+            // aligned up to this stay aligned when the buffer moves. Stricter
+            // types raise it in `alloc_or_grow`.
+            arena: Arena::with_capacity_aligned(capacity, 16)?,
+        })
+    }
+
+    /// Moves `value` into the arena; see [`GrowableArena::alloc_or_grow`].
+    pub fn alloc<T>(&mut self, value: T) -> Handle<T> {
+        self.alloc_or_grow(value)
+    }
+
+    /// Moves `value` into the arena, doubling the buffer first only if it
+    /// does not fit. Growing moves every value, which is why this returns a
+    /// [`Handle`] and takes `&mut self`. Like `Vec`, running out of memory
+    /// aborts via [`handle_alloc_error`].
+    pub fn alloc_or_grow<T>(&mut self, value: T) -> Handle<T> {
+        let layout = Layout::new::<T>();
+        // Moving only keeps the base alignment, so raise it before placing
+        // the first value that needs more, while no existing one does. Both
+        // that and growing are done in one move.
+        let base_align = self.arena.allocation.align();
+        let align = base_align.max(layout.align());
+        if align > base_align || self.arena.classify_fit(layout) != FitResult::Fits {
+            // On the moved buffer the value needs at most `align - 1` bytes
+            // of padding.
+            let Some(needed) = self
+                .arena
+                .used()
+                .checked_add(layout.size() + layout.align() - 1)
+            else {
+                handle_alloc_error(layout);
+            };
+            let capacity = if needed <= self.arena.capacity() {
+                self.arena.capacity()
+            } else {
+                needed.max(self.arena.capacity().saturating_mul(2))
+            };
+            if self.arena.relocate_aligned(capacity, align).is_err() {
+                handle_alloc_error(Layout::from_size_align(capacity, align).unwrap_or(layout));
+            }
+        }
+        match self.arena.alloc_node(value) {
+            Ok(node) => Handle(node),
+            Err(_) => handle_alloc_error(layout),
+        }
+    }
+
+    /// Frees every value, keeping the buffer. Handles allocated before are
+    /// stale afterwards.
+    pub fn reset(&mut self) {
+        self.arena.reset();
+    }
+
+    /// The value behind `handle`.
+    ///
+    /// As with [`Arena::get`], a handle from another arena is not detected,
+    /// and using one is unsafe in spirit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `handle` lies outside this arena's used bytes or predates a
+    /// [`GrowableArena::reset`].
+    pub fn get<T>(&self, handle: Handle<T>) -> &T {
+        unsafe { &*self.ptr(handle) }
+    }
+
+    pub fn get_mut<T>(&mut self, handle: Handle<T>) -> &mut T {
+        unsafe { &mut *self.ptr(handle) }
+    }
+
+    fn ptr<T>(&self, handle: Handle<T>) -> *mut T {
+        self.arena
+            .node_ptr(handle.0)
+            .expect("stale handle or handle outside the arena")
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    pub fn used(&self) -> usize {
+        self.arena.used()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GrowableArena;
+
+    #[test]
+    fn test_doubles_when_full() {
+        let mut arena = GrowableArena::with_capacity(16).unwrap();
+        let mut handles = Vec::new();
+        for i in 0..100u64 {
+            handles.push(arena.alloc(i * 3));
+        }
+        assert!(arena.capacity() >= 800);
+        assert!(arena.capacity() <= 2048);
+        *arena.get_mut(handles[10]) += 1;
+        for (i, &handle) in handles.iter().enumerate() {
+            let expected = i as u64 * 3 + u64::from(i == 10);
+            assert_eq!(*arena.get(handle), expected);
+        }
+    }
+
+    #[test]
+    fn test_over_aligned_values_survive_doubling() {
+        #[repr(align(64))]
+        struct Line(u8);
+
+        let mut arena = GrowableArena::with_capacity(16).unwrap();
+        let mut handles = Vec::new();
+        for i in 0..20u8 {
+            handles.push(arena.alloc(Line(i)));
+            arena.alloc(i);
+        }
+        assert!(arena.capacity() >= 20 * 128);
+        for (i, &handle) in handles.iter().enumerate() {
+            let line = arena.get(handle);
+            assert_eq!(line as *const Line as usize % 64, 0);
+            assert_eq!(line.0, i as u8);
+        }
+    }
+
+    #[test]
+    fn test_zero_sized_values() {
+        let mut arena = GrowableArena::with_capacity(0).unwrap();
+        let unit = arena.alloc(());
+        assert_eq!(*arena.get(unit), ());
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_alloc_or_grow() {
+        let mut arena = GrowableArena::with_capacity(8).unwrap();
+        let mut handles = Vec::new();
+        let mut capacities = vec![arena.capacity()];
+        for i in 0..200u32 {
+            handles.push(arena.alloc_or_grow((i, i as u8)));
+            if arena.capacity() != *capacities.last().unwrap() {
+                capacities.push(arena.capacity());
+            }
+        }
+        assert!(capacities.len() > 3, "{capacities:?}");
+        for (i, &handle) in handles.iter().enumerate() {
+            assert_eq!(*arena.get(handle), (i as u32, i as u8));
+        }
+    }
+
+    #[test]
+    fn test_alloc_or_grow_over_aligned() {
+        #[repr(align(128))]
+        struct Page(u32);
+
+        // The first page fits without growing, but still moves the buffer
+        // to a stricter base.
+        let mut arena = GrowableArena::with_capacity(512).unwrap();
+        let byte = arena.alloc_or_grow(1u8);
+        let first = arena.alloc_or_grow(Page(0));
+        assert_eq!(arena.capacity(), 512);
+        let mut pages = vec![first];
+        for i in 1..10 {
+            pages.push(arena.alloc_or_grow(Page(i)));
+        }
+        assert!(arena.capacity() > 512);
+        assert_eq!(*arena.get(byte), 1);
+        for (i, &page) in pages.iter().enumerate() {
+            let page = arena.get(page);
+            assert_eq!(page as *const Page as usize % 128, 0);
+            assert_eq!(page.0, i as u32);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "stale handle")]
+    fn test_reset_makes_handles_stale() {
+        let mut arena = GrowableArena::with_capacity(16).unwrap();
+        let stale = arena.alloc(1u32);
+        arena.reset();
+        let fresh = arena.alloc(2u32);
+        assert_eq!(*arena.get(fresh), 2);
+        arena.get(stale);
+    }
+
+    #[test]
+    fn test_grows_from_empty_for_large_values() {
+        let mut arena = GrowableArena::with_capacity(0).unwrap();
+        let small = arena.alloc(1u8);
+        let big = arena.alloc([7u32; 64]);
+        assert_eq!(*arena.get(small), 1);
+        assert_eq!(arena.get(big)[63], 7);
+    }
+}
@@ -0,0 +1,129 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::{Cell, UnsafeCell},
+    mem::MaybeUninit,
+    ptr::NonNull,
+};
+
+use crate::padding_for_parts;
+
+/// A bump allocator whose `N` byte buffer is stored inline, so it needs no
+/// heap at all: it lives on the stack or inside another value, and can be
+/// built in a `const` context.
+///
+/// Like [`SyncArena`](crate::SyncArena) it never reclaims single blocks;
+/// [`InlineArena::reset`] frees everything at once.
+#[derive(Debug)]
+pub struct InlineArena<const N: usize> {
+    offset: Cell<usize>,
+    buffer: UnsafeCell<[MaybeUninit<u8>; N]>,
+}
+
+impl<const N: usize> InlineArena<N> {
+    pub const fn new() -> Self {
+        Self {
+            offset: Cell::new(0),
+            buffer: UnsafeCell::new([MaybeUninit::uninit(); N]),
+        }
+    }
+
+    pub fn can_fit<T>(&self) -> bool {
+        self.padding(Layout::new::<T>()).is_some()
+    }
+
+    /// Total size of the buffer in bytes, which is always `N`.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Bytes handed out so far, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// Bytes left in the arena; see [`Arena::remaining`](crate::Arena::remaining).
+    pub fn remaining(&self) -> usize {
+        N - self.offset.get()
+    }
+
+    /// Rewinds the arena to empty. Like [`Arena::reset`](crate::Arena::reset)
+    /// this takes `&mut self`, so nothing allocated can still be borrowed.
+    pub fn reset(&mut self) {
+        self.offset.set(0);
+    }
+
+    fn base_ptr(&self) -> *mut u8 {
+        self.buffer.get().cast()
+    }
+
+    fn padding(&self, layout: Layout) -> Option<usize> {
+        padding_for_parts(
+            core::ptr::slice_from_raw_parts(self.base_ptr(), N),
+            self.offset.get(),
+            layout.size(),
+            layout.align(),
+        )
+    }
+}
+
+impl<const N: usize> Default for InlineArena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<const N: usize> Allocator for &InlineArena<N> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(layout.dangling_ptr(), 0));
+        }
+        let padding = self.padding(layout).ok_or(AllocError)?;
+        let start = self.offset.get() + padding;
+        self.offset.set(start + layout.size());
+        let ptr = unsafe { NonNull::new_unchecked(self.base_ptr().add(start)) };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{Allocator, Layout};
+
+    use crate::{required_capacity, InlineArena};
+
+    #[test]
+    fn it_works() {
+        let arena = InlineArena::<64>::new();
+        let a = Box::new_in(5u64, &arena);
+        let b = Box::new_in([1u16; 3], &arena);
+        assert_eq!((*a, *b), (5, [1, 1, 1]));
+        assert_eq!(&*a as *const u64 as usize % 8, 0);
+        // The buffer is only byte-aligned, so the first block may be padded.
+        let padding = &*a as *const u64 as usize - arena.base_ptr() as usize;
+        assert_eq!(arena.used(), padding + 14);
+        let rest = Layout::array::<u8>(arena.remaining() + 1).unwrap();
+        assert!((&arena).allocate(rest).is_err());
+        assert!(arena.can_fit::<[u8; 43]>());
+    }
+
+    #[test]
+    fn test_const_construction() {
+        let mut arena = const { InlineArena::<16>::new() };
+        drop(Box::new_in(1u32, &arena));
+        arena.reset();
+        assert_eq!(arena.remaining(), 16);
+    }
+
+    #[test]
+    fn test_required_capacity() {
+        const N: usize = required_capacity(8, 8, 10);
+        let buffer = [0u8; N];
+        assert_eq!(buffer.len(), 87);
+
+        let arena = InlineArena::<N>::new();
+        let boxes: Vec<_> = (0..10u64).map(|i| Box::new_in(i, &arena)).collect();
+        assert_eq!(boxes.iter().map(|b| **b).sum::<u64>(), 45);
+    }
+}
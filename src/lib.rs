@@ -1,31 +1,779 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![feature(allocator_api)]
-#![feature(alloc_layout_extra)]
-use std::{
+#![feature(ptr_metadata)]
+
+extern crate alloc;
+
+mod builder;
+mod bytes;
+mod chunked;
+mod collect;
+#[cfg(feature = "debug-checks")]
+mod debug;
+mod error;
+mod frozen;
+mod growable;
+mod inline;
+#[cfg(all(feature = "mmap", unix))]
+mod mapped;
+mod strings;
+mod sync;
+mod typed;
+
+pub use builder::ArenaBuilder;
+pub use bytes::{ArenaBytes, ArenaWriter};
+pub use chunked::ChunkedArena;
+pub use collect::ArenaCollect;
+#[cfg(feature = "debug-checks")]
+pub use debug::DebugArena;
+pub use error::ArenaError;
+pub use frozen::FrozenArena;
+pub use growable::{GrowableArena, Handle};
+pub use inline::InlineArena;
+#[cfg(all(feature = "mmap", unix))]
+pub use mapped::MappedArena;
+pub use strings::StringArena;
+pub use sync::{SharedArena, SyncArena};
+pub use typed::TypedArena;
+
+#[cfg(feature = "tracking")]
+use alloc::collections::BTreeMap;
+use alloc::{
+    alloc::{handle_alloc_error, Global},
+    boxed::Box,
+    rc::Rc,
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
     alloc::{AllocError, Allocator, Layout},
-    cell::Cell,
-    ptr::NonNull,
+    cell::{Cell, RefCell},
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::Deref,
+    ptr::{NonNull, Pointee},
+    sync::atomic::{compiler_fence, Ordering},
 };
 
 #[derive(Debug)]
-pub struct Arena {
+pub struct Arena<'a, A: Allocator = Global> {
     offset: Cell<usize>,
-    allocation: Box<[u8]>,
+    /// Start of the region allocated downward from the end of the buffer by
+    /// [`Arena::allocate_down`]; the free space is `offset..top`.
+    top: Cell<usize>,
+    /// Largest alignment allocated downward, kept so relocating the top
+    /// region preserves it.
+    top_align: Cell<usize>,
+    peak: Cell<usize>,
+    /// Like `peak`, but only since the last [`Arena::reset_adaptive`].
+    cycle_peak: Cell<usize>,
+    /// Peaks of the last cycles ended by [`Arena::reset_adaptive`], written
+    /// round-robin.
+    recent_peaks: [usize; RECENT_PEAKS],
+    /// Cycles ended by [`Arena::reset_adaptive`].
+    cycles: usize,
+    /// Bytes in `start..end` have not been touched since the buffer was
+    /// zeroed, so `allocate_zeroed` can skip them. Empty unless the arena
+    /// came from [`Arena::with_capacity_zeroed`].
+    zeroed: Cell<(usize, usize)>,
+    /// Alignment padding inserted since the last reset.
+    wasted: Cell<usize>,
+    /// Non-empty blocks handed out since the last reset.
+    allocations: Cell<usize>,
+    /// Bytes handed out since creation, padding included, for
+    /// [`Arena::lifetime_allocated`].
+    lifetime_bytes: Cell<u64>,
+    /// Every block starts on a multiple of this, set by
+    /// [`ArenaBuilder::granularity`].
+    granularity: usize,
+    /// Blocks handed out through the `Allocator` impl, and so by `Box`,
+    /// `Vec` and the like, that have not been deallocated yet.
+    live: Cell<usize>,
+    /// Offset recorded by [`Arena::mark_persistent`].
+    base: Cell<usize>,
+    /// Bumped whenever the upward region is reset or rewound over a node, so
+    /// a [`NodeRef`] from before can be told apart.
+    epoch: Cell<u64>,
+    /// End of the highest [`NodeRef`] allocated in the current epoch, so
+    /// rewinds that stay above it keep the epoch.
+    nodes_end: Cell<usize>,
+    /// Start and layout of every live block, for
+    /// [`Arena::allocations_iter`].
+    #[cfg(feature = "tracking")]
+    record: RefCell<Vec<(usize, Layout)>>,
+    /// Calls to `Allocator::deallocate`, for [`Arena::dealloc_count`].
+    #[cfg(feature = "tracking")]
+    dealloc_calls: Cell<usize>,
+    /// Offsets saved by [`Arena::push_scope`], innermost last.
+    scopes: RefCell<Vec<usize>>,
+    /// Start offsets recorded by [`Arena::new_generation`], oldest first.
+    generations: RefCell<Vec<usize>>,
+    allocation: Storage<'a>,
+    /// Allocates and frees the owned backing buffer.
+    alloc: A,
+}
+
+/// The buffer an [`Arena`] bumps into: either allocated by the arena itself
+/// or lent to it by the caller.
+#[derive(Debug)]
+enum Storage<'a> {
+    /// Freed by the arena's allocator with the exact layout it was allocated
+    /// with, which a `Box<[u8]>` could not do for a larger than byte
+    /// alignment.
+    Owned { ptr: NonNull<u8>, layout: Layout },
+    /// Kept as a raw pointer rather than the `&mut [u8]` it came from, so
+    /// pointers handed out through `&self` carry write permission, and
+    /// looking at the buffer never asserts shared access over blocks that
+    /// are still borrowed.
+    Borrowed {
+        ptr: NonNull<u8>,
+        len: usize,
+        buf: PhantomData<&'a mut [u8]>,
+    },
+    /// An anonymous mapping, unmapped on drop.
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped { ptr: NonNull<u8>, len: usize },
+    /// Part of a buffer cut up by [`Arena::split_off`], freed once the last
+    /// part is dropped.
+    Shared {
+        ptr: NonNull<u8>,
+        len: usize,
+        owner: Arc<SplitBuffer>,
+    },
+}
+
+/// The whole buffer behind [`Storage::Shared`] parts. Holds owned storage
+/// from the global allocator or a mapping, never a borrowed one.
+#[derive(Debug)]
+struct SplitBuffer(Storage<'static>);
+
+impl Drop for SplitBuffer {
+    fn drop(&mut self) {
+        if let Storage::Owned { ptr, layout } = self.0 {
+            if layout.size() != 0 {
+                unsafe { Global.deallocate(ptr, layout) };
+            }
+        }
+    }
+}
+
+// SAFETY: an owned buffer is uniquely owned by the storage, like a
+// `Box<[u8]>`, and shared access only reads it, so it is as thread-safe as a
+// box; the borrowed case is a `&mut [u8]`, which already is.
+unsafe impl Send for Storage<'_> {}
+unsafe impl Sync for Storage<'_> {}
+
+impl<'a> Storage<'a> {
+    fn borrowed(buf: &'a mut [u8]) -> Self {
+        Storage::Borrowed {
+            len: buf.len(),
+            ptr: NonNull::from(buf).cast(),
+            buf: PhantomData,
+        }
+    }
+}
+
+impl Storage<'_> {
+    fn owned(capacity: usize, align: usize) -> Result<Self, AllocError> {
+        Self::owned_in(capacity, align, &Global)
+    }
+
+    /// Like [`Storage::owned`], but allocated by `alloc`.
+    fn owned_in(capacity: usize, align: usize, alloc: &impl Allocator) -> Result<Self, AllocError> {
+        Self::allocate(capacity, align, alloc, false)
+    }
+
+    /// Allocates `capacity` bytes aligned to `align` from `alloc`, zeroed if
+    /// `zeroed` is set.
+    fn allocate(
+        capacity: usize,
+        align: usize,
+        alloc: &impl Allocator,
+        zeroed: bool,
+    ) -> Result<Self, AllocError> {
+        let layout = Layout::from_size_align(capacity, align).map_err(|_| AllocError)?;
+        // An empty buffer never touches the allocator, so zero-sized layouts
+        // need no special case in the allocator or when freeing.
+        let ptr = match (capacity, zeroed) {
+            (0, _) => layout.dangling_ptr(),
+            (_, false) => alloc.allocate(layout)?.cast(),
+            (_, true) => alloc.allocate_zeroed(layout)?.cast(),
+        };
+        Ok(Storage::Owned { ptr, layout })
+    }
+
+    /// The alignment the buffer was requested with.
+    fn align(&self) -> usize {
+        match self {
+            Storage::Owned { layout, .. } => layout.align(),
+            Storage::Borrowed { .. } | Storage::Shared { .. } => 1,
+            #[cfg(all(feature = "mmap", unix))]
+            Storage::Mapped { .. } => 1,
+        }
+    }
+
+    /// The buffer's size together with [`Storage::align`].
+    fn layout(&self) -> Layout {
+        match self {
+            Storage::Owned { layout, .. } => *layout,
+            // Any length that exists in memory is a valid size at align 1.
+            _ => unsafe { Layout::from_size_align_unchecked(self.len(), self.align()) },
+        }
+    }
+
+    /// Start of the buffer. Every pointer into it is derived from this one,
+    /// so its provenance covers the whole buffer, for writes too.
+    fn as_ptr(&self) -> *mut u8 {
+        match self {
+            Storage::Owned { ptr, .. }
+            | Storage::Borrowed { ptr, .. }
+            | Storage::Shared { ptr, .. } => ptr.as_ptr(),
+            #[cfg(all(feature = "mmap", unix))]
+            Storage::Mapped { ptr, .. } => ptr.as_ptr(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Storage::Owned { layout, .. } => layout.size(),
+            Storage::Borrowed { len, .. } | Storage::Shared { len, .. } => *len,
+            #[cfg(all(feature = "mmap", unix))]
+            Storage::Mapped { len, .. } => *len,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The first `len` bytes, as a raw slice that asserts nothing about
+    /// blocks still borrowed.
+    fn prefix(&self, len: usize) -> *const [u8] {
+        debug_assert!(len <= self.len());
+        core::ptr::slice_from_raw_parts(self.as_ptr(), len)
+    }
+
+    /// The bytes in `range`.
+    ///
+    /// # Safety
+    ///
+    /// They must be initialized and not mutably borrowed while the slice is
+    /// alive.
+    unsafe fn slice(&self, range: core::ops::Range<usize>) -> &[u8] {
+        debug_assert!(range.start <= range.end && range.end <= self.len());
+        unsafe { core::slice::from_raw_parts(self.as_ptr().add(range.start), range.len()) }
+    }
+}
+
+impl<'a> Storage<'a> {
+    /// Cuts the buffer into `[0, at)` and `[at, len)`. Borrowed parts keep
+    /// borrowing; anything else moves into a [`SplitBuffer`] both parts
+    /// share. Owned storage must come from the global allocator.
+    fn split(self, at: usize) -> (Self, Self) {
+        debug_assert!(at <= self.len());
+        let this = core::mem::ManuallyDrop::new(self);
+        let (ptr, len) = (this.as_ptr(), this.len());
+        let owner = match &*this {
+            Storage::Borrowed { .. } => None,
+            Storage::Owned { ptr, layout } => Some(Arc::new(SplitBuffer(Storage::Owned {
+                ptr: *ptr,
+                layout: *layout,
+            }))),
+            #[cfg(all(feature = "mmap", unix))]
+            Storage::Mapped { ptr, len } => Some(Arc::new(SplitBuffer(Storage::Mapped {
+                ptr: *ptr,
+                len: *len,
+            }))),
+            // `this` is never dropped, so its reference moves out here.
+            Storage::Shared { owner, .. } => Some(unsafe { core::ptr::read(owner) }),
+        };
+        let part = |start: usize, len: usize| {
+            let ptr = unsafe { NonNull::new_unchecked(ptr.add(start)) };
+            match &owner {
+                None => Storage::Borrowed {
+                    ptr,
+                    len,
+                    buf: PhantomData,
+                },
+                Some(owner) => Storage::Shared {
+                    ptr,
+                    len,
+                    owner: owner.clone(),
+                },
+            }
+        };
+        (part(0, at), part(at, len - at))
+    }
+}
+
+/// Owned buffers are freed by the arena, which holds the allocator.
+impl Drop for Storage<'_> {
+    fn drop(&mut self) {
+        match self {
+            #[cfg(all(feature = "mmap", unix))]
+            Storage::Mapped { ptr, len } if *len != 0 => unsafe { mapped::unmap(*ptr, *len) },
+            _ => {}
+        }
+    }
+}
+
+/// A snapshot of an [`Arena`]'s counters, returned by [`Arena::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub capacity: usize,
+    pub used: usize,
+    pub remaining: usize,
+    pub peak: usize,
+    pub wasted: usize,
+    pub allocations: usize,
+}
+
+/// A capacity suggested by [`Arena::reset_adaptive`] from recent usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeHint {
+    pub suggested_capacity: usize,
+}
+
+/// How many cycles [`Arena::reset_adaptive`] looks back over.
+const RECENT_PEAKS: usize = 8;
+
+/// Whether a layout fits in an [`Arena`], returned by [`Arena::classify_fit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitResult {
+    Fits,
+    /// Fits once the buffer has grown by this many bytes.
+    NeedsGrowth(usize),
+    /// The block would end past `isize::MAX`, so no capacity could hold it.
+    ImpossibleOverflow,
+}
+
+/// A saved position in an [`Arena`], created by [`Arena::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker(usize);
+
+/// Guard returned by [`Arena::scope`] that rewinds the arena when dropped.
+///
+/// It derefs to the arena; allocating through the guard ties the results to
+/// its lifetime, so they cannot outlive the rewind. The guard holds the
+/// arena's only borrow, so nothing can be allocated around it either:
+///
+/// ```compile_fail
+/// # use arena::Arena;
+/// let mut arena = Arena::with_capacity(64).unwrap();
+/// let scope = arena.scope();
+/// let outside = arena.alloc(1u32).unwrap();
+/// drop(scope);
+/// ```
+#[derive(Debug)]
+pub struct Scope<'s, A: Allocator = Global> {
+    arena: &'s Arena<'s, A>,
+    marker: Marker,
+}
+
+impl<'s, A: Allocator> Deref for Scope<'s, A> {
+    type Target = Arena<'s, A>;
+
+    fn deref(&self) -> &Arena<'s, A> {
+        self.arena
+    }
+}
+
+impl<A: Allocator> Drop for Scope<'_, A> {
+    fn drop(&mut self) {
+        // `scope` borrowed the arena mutably, so everything allocated since
+        // went through the guard and borrows it: none of it is alive here.
+        unsafe { self.arena.rewind(self.marker) };
+    }
+}
+
+/// Allocator returned by [`Arena::frame`] that rewinds the arena when
+/// dropped.
+///
+/// Where a [`Scope`] is used through its arena, a frame is the allocator
+/// itself: collections built with `&frame` borrow the frame, so they cannot
+/// outlive the rewind.
+///
+/// ```compile_fail
+/// # use arena::Arena;
+/// let mut arena = Arena::with_capacity(64).unwrap();
+/// let escaped = {
+///     let frame = arena.frame();
+///     let mut v = Vec::new_in(&frame);
+///     v.push(1u32);
+///     v
+/// };
+/// ```
+///
+/// Nor can the arena be used directly while the frame lives:
+///
+/// ```compile_fail
+/// # use arena::Arena;
+/// let mut arena = Arena::with_capacity(64).unwrap();
+/// let frame = arena.frame();
+/// let outside = arena.alloc(1u32).unwrap();
+/// drop(frame);
+/// ```
+#[derive(Debug)]
+pub struct Frame<'f, A: Allocator = Global> {
+    arena: &'f Arena<'f, A>,
+    marker: Marker,
+}
+
+impl<A: Allocator> Drop for Frame<'_, A> {
+    fn drop(&mut self) {
+        // `frame` borrowed the arena mutably, so every block since came from
+        // the frame and borrows it: none is alive.
+        unsafe { self.arena.rewind(self.marker) };
+    }
+}
+
+/// A `T` in an [`Arena`], stored as its offset from the start of the buffer,
+/// created by [`Arena::alloc_node`]. Unlike a reference it stays valid when
+/// the arena is relocated by [`Arena::grow_to`], so linked structures can
+/// store it in place of pointers.
+///
+/// Relocation keeps the buffer's base alignment and nothing more, so this
+/// only holds if that is at least `align_of::<T>()`, e.g. with
+/// [`Arena::with_capacity_aligned`]. A byte-aligned arena from
+/// [`Arena::with_capacity`] or [`Arena::from_buffer`] may move a `u64` to
+/// an odd address, and [`Arena::get`] then returns `None`.
+///
+/// It also remembers how often the arena had been reset or rewound over a
+/// node, so [`Arena::get`] rejects a node from before one of those instead of
+/// reading whatever took its place. This is tracked for all nodes at once: a
+/// rewind that frees any node makes every older one stale, while one that
+/// frees none keeps them all.
+pub struct NodeRef<T>(usize, u64, PhantomData<fn() -> T>);
+
+impl<T> NodeRef<T> {
+    /// Offset of the value from the start of the buffer.
+    pub fn offset(self) -> usize {
+        self.0
+    }
+}
+
+// Derives would require `T` to implement the traits as well.
+impl<T> Clone for NodeRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NodeRef<T> {}
+
+impl<T> PartialEq for NodeRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.0, self.1) == (other.0, other.1)
+    }
+}
+
+impl<T> Eq for NodeRef<T> {}
+
+impl<T> fmt::Debug for NodeRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NodeRef").field(&self.0).finish()
+    }
 }
 
-impl Arena {
+impl Arena<'_> {
+    /// Creates an empty arena with no capacity, to be sized later with
+    /// [`Arena::grow_to`].
+    pub fn new() -> Self {
+        Self::with_capacity(0).unwrap()
+    }
+
     pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
-        let layout = std::alloc::Layout::array::<u8>(capacity).map_err(|_| AllocError)?;
-        let allocation: Box<[u8]> = unsafe {
-            Box::from_raw(std::slice::from_raw_parts_mut(
-                std::alloc::alloc(layout),
-                capacity,
-            ))
+        Ok(Self::from_storage(Storage::owned(capacity, 1)?))
+    }
+
+    /// Like [`Arena::with_capacity`], but the backing buffer starts at a
+    /// multiple of `align`, so a first allocation with that alignment needs
+    /// no padding. `align` must be a power of two.
+    pub fn with_capacity_aligned(capacity: usize, align: usize) -> Result<Self, AllocError> {
+        Ok(Self::from_storage(Storage::owned(capacity, align)?))
+    }
+
+    /// Capacity that holds `n` separate `T`s whatever the base alignment:
+    /// the worst-case padding before the first one plus their sizes.
+    /// Saturates at `usize::MAX`, which no allocation can satisfy anyway.
+    pub const fn capacity_for<T>(n: usize) -> usize {
+        required_capacity(core::mem::size_of::<T>(), core::mem::align_of::<T>(), n)
+    }
+
+    /// Like [`Arena::with_capacity`], but the backing buffer is zeroed up
+    /// front with `alloc_zeroed`, which the system can often satisfy with
+    /// fresh zero pages. Zeroed allocations from untouched space then skip
+    /// zeroing.
+    pub fn with_capacity_zeroed(capacity: usize) -> Result<Self, AllocError> {
+        ArenaBuilder::new().capacity(capacity).zeroed(true).build()
+    }
+
+    /// Starts configuring an arena; see [`ArenaBuilder`].
+    pub fn builder() -> ArenaBuilder {
+        ArenaBuilder::new()
+    }
+
+    /// Layout of `[T; n]`, or [`ArenaError::LayoutOverflow`] if it cannot be
+    /// represented.
+    fn array_layout<T>(n: usize) -> Result<Layout, ArenaError> {
+        Layout::array::<T>(n).map_err(|_| ArenaError::LayoutOverflow)
+    }
+
+    /// Takes over `buf` as the backing buffer, e.g. one recycled from a
+    /// previous arena with [`Arena::into_boxed_slice`], so no fresh
+    /// allocation is needed. The arena frees it like its own.
+    pub fn from_boxed_slice(buf: Box<[u8]>) -> Self {
+        let layout = Layout::for_value(&*buf);
+        let ptr = NonNull::new(Box::into_raw(buf).cast::<u8>()).unwrap();
+        Self::from_storage(Storage::Owned { ptr, layout })
+    }
+
+    /// Like [`Arena::from_boxed_slice`], for a buffer that was never
+    /// initialized, e.g. from `Box::new_uninit_slice`.
+    pub fn from_boxed_uninit(buf: Box<[MaybeUninit<u8>]>) -> Self {
+        let layout = Layout::for_value(&*buf);
+        let ptr = NonNull::new(Box::into_raw(buf).cast::<u8>()).unwrap();
+        Self::from_storage(Storage::Owned { ptr, layout })
+    }
+
+    /// Like [`Arena::into_boxed_slice`], but without claiming the bytes are
+    /// initialized: padding and space never handed out were never written,
+    /// so reading them as `u8` is undefined behavior.
+    pub fn into_boxed_uninit(mut self) -> Box<[MaybeUninit<u8>]> {
+        match self.allocation {
+            Storage::Owned { ptr, layout } if layout.align() == 1 && layout.size() != 0 => {
+                core::mem::forget(core::mem::replace(
+                    &mut self.allocation,
+                    Storage::borrowed(&mut []),
+                ));
+                unsafe {
+                    Box::from_raw(core::ptr::slice_from_raw_parts_mut(
+                        ptr.as_ptr().cast(),
+                        layout.size(),
+                    ))
+                }
+            }
+            ref allocation => {
+                let mut copy = Box::new_uninit_slice(allocation.len());
+                unsafe {
+                    allocation
+                        .as_ptr()
+                        .cast::<MaybeUninit<u8>>()
+                        .copy_to_nonoverlapping(copy.as_mut_ptr(), allocation.len())
+                };
+                copy
+            }
+        }
+    }
+
+    /// Consumes the arena and returns its whole backing buffer. An owned
+    /// byte-aligned buffer is handed over as is; aligned or borrowed storage
+    /// is copied.
+    ///
+    /// # Safety
+    ///
+    /// Every byte of the buffer must be initialized: the arena started from
+    /// initialized bytes, e.g. [`Arena::with_capacity_zeroed`] or
+    /// [`Arena::from_boxed_slice`], or everything up to the capacity was
+    /// handed out and written. See [`Arena::used_bytes`].
+    pub unsafe fn into_boxed_slice(self) -> Box<[u8]> {
+        unsafe { self.into_boxed_uninit().assume_init() }
+    }
+
+    /// Ends the build phase: consumes the arena and keeps its upward used
+    /// bytes as an immutable [`FrozenArena`] that can be shared across
+    /// threads.
+    pub fn freeze(self) -> FrozenArena {
+        FrozenArena::new(self.into_used_uninit())
+    }
+
+    /// Like [`Arena::into_boxed_slice`], trimmed to the bytes handed out
+    /// from the upward region.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Arena::used_bytes`]: every block in the upward region must
+    /// be fully initialized.
+    pub unsafe fn into_used(self) -> Box<[u8]> {
+        unsafe { self.into_used_uninit().assume_init() }
+    }
+
+    fn into_used_uninit(self) -> Box<[MaybeUninit<u8>]> {
+        let used = self.offset.get();
+        let mut buffer = self.into_boxed_uninit().into_vec();
+        buffer.truncate(used);
+        buffer.into_boxed_slice()
+    }
+}
+
+impl<A: Allocator> Arena<'_, A> {
+    /// Like [`Arena::with_capacity`], but the backing buffer comes from
+    /// `alloc` rather than the global allocator, e.g. a pool or another
+    /// arena. The arena frees it with `alloc` when dropped.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Result<Self, AllocError> {
+        let allocation = Storage::owned_in(capacity, 1, &alloc)?;
+        Ok(Self::from_storage_in(allocation, alloc))
+    }
+
+    /// Moves the arena into a larger backing buffer, keeping the bytes
+    /// allocated so far. Does nothing if `new_capacity` is not larger than
+    /// the current capacity.
+    ///
+    /// This relocates the buffer, so every pointer handed out before the
+    /// call is invalidated; taking `&mut self` guarantees none are still
+    /// borrowed. The new buffer has the old one's base alignment, which is
+    /// what keeps [`NodeRef`]s valid.
+    pub fn grow_to(&mut self, new_capacity: usize) -> Result<(), AllocError> {
+        if new_capacity <= self.allocation.len() {
+            return Ok(());
+        }
+        self.relocate(new_capacity)
+    }
+
+    /// Moves the arena into a buffer just large enough for what is allocated
+    /// in it, handing the rest back to the allocator; the inverse of
+    /// [`Arena::grow_to`]. The downward region may keep a few bytes of
+    /// padding to stay aligned.
+    ///
+    /// Like `grow_to` this invalidates every pointer into the arena.
+    pub fn shrink_to_used(&mut self) -> Result<(), AllocError> {
+        let top_len = self.allocation.len() - self.top.get();
+        let capacity = match top_len {
+            0 => self.offset.get(),
+            // The new base may sit anywhere modulo the alignment, so leave
+            // room for the worst case.
+            _ => self.offset.get() + self.top_align.get() - 1 + top_len,
         };
+        if capacity >= self.allocation.len() {
+            return Ok(());
+        }
+        self.relocate(capacity)
+    }
 
-        Ok(Self {
-            offset: Cell::new(0),
-            allocation,
-        })
+    fn relocate(&mut self, capacity: usize) -> Result<(), AllocError> {
+        self.relocate_aligned(capacity, self.allocation.align())
+    }
+
+    /// Moves the arena into a buffer of `capacity` bytes whose base is
+    /// aligned to `align`, which later moves keep, so offsets aligned to it
+    /// stay aligned. Like [`Arena::grow_to`] this invalidates every pointer
+    /// into the arena.
+    pub(crate) fn relocate_aligned(
+        &mut self,
+        capacity: usize,
+        align: usize,
+    ) -> Result<(), AllocError> {
+        let (allocation, top) = self.relocated(capacity, align)?;
+        self.replace_storage(allocation);
+        #[cfg(feature = "tracking")]
+        for (start, _) in self.record.get_mut() {
+            if *start >= self.top.get() {
+                *start = *start - self.top.get() + top;
+            }
+        }
+        self.top.set(top);
+        self.zeroed.set((0, 0));
+        Ok(())
+    }
+
+    /// Resets the arena and, if `new_capacity` is larger, swaps in a bigger
+    /// buffer with the same base alignment. Unlike [`Arena::grow_to`] the old
+    /// contents are not copied. On error the arena is left untouched.
+    pub fn clear_and_grow(&mut self, new_capacity: usize) -> Result<(), AllocError> {
+        if new_capacity > self.allocation.len() {
+            let allocation = Storage::owned_in(new_capacity, self.allocation.align(), &self.alloc)?;
+            self.replace_storage(allocation);
+            self.zeroed.set((0, 0));
+        }
+        self.reset();
+        Ok(())
+    }
+
+    /// Resets the arena and, if [`Arena::peak_used`] plus `slack` times as
+    /// much again is below the capacity, swaps in a buffer of that size, e.g.
+    /// to hand memory back between bursts of a long-running service. The
+    /// old contents are not copied. On error the arena is left untouched.
+    pub fn reset_and_right_size(&mut self, slack: f32) -> Result<(), AllocError> {
+        let peak = self.peak_used();
+        let target = ((peak as f64 * (1.0 + f64::from(slack))) as usize).max(peak);
+        if target >= self.allocation.len() {
+            self.reset();
+            return Ok(());
+        }
+        let allocation = Storage::owned_in(target, self.allocation.align(), &self.alloc)?;
+        // Reset against the old buffer, whose bounds the offsets refer to.
+        self.reset();
+        self.replace_storage(allocation);
+        self.top.set(target);
+        self.zeroed.set((0, 0));
+        Ok(())
+    }
+
+    /// Resets the arena at the end of a cycle of work and suggests a
+    /// capacity from the highest usage of the last few cycles: twice the
+    /// capacity once that peak passes three quarters of it, twice the peak
+    /// once it drops below a quarter, and the current capacity otherwise.
+    /// Acting on it, e.g. with [`Arena::grow_to`] or
+    /// [`Arena::shrink_to_used`], is up to the caller.
+    pub fn reset_adaptive(&mut self) -> SizeHint {
+        let peak = self.cycle_peak.get().max(self.used());
+        self.recent_peaks[self.cycles % RECENT_PEAKS] = peak;
+        self.cycles += 1;
+        self.reset();
+        self.cycle_peak.set(0);
+
+        let capacity = self.allocation.len();
+        let recent = &self.recent_peaks[..self.cycles.min(RECENT_PEAKS)];
+        let max_peak = recent.iter().copied().max().unwrap_or(0);
+        let suggested_capacity = if max_peak > capacity / 4 * 3 {
+            capacity.saturating_mul(2)
+        } else if max_peak < capacity / 4 {
+            max_peak.saturating_mul(2)
+        } else {
+            capacity
+        };
+        SizeHint { suggested_capacity }
+    }
+
+    /// A new buffer of `capacity` bytes with the same base alignment, holding
+    /// a copy of the used bytes, together with the new top offset.
+    ///
+    /// The downward region stays at the end of the buffer, moved by a multiple
+    /// of its largest alignment.
+    fn relocated(
+        &self,
+        capacity: usize,
+        align: usize,
+    ) -> Result<(Storage<'static>, usize), AllocError> {
+        let top_len = self.allocation.len() - self.top.get();
+        let max_top = capacity.checked_sub(top_len).ok_or(AllocError)?;
+        let allocation = Storage::owned_in(capacity, align, &self.alloc)?;
+        // The downward region was aligned by address, not by offset, so it
+        // goes to the same address modulo its alignment: for a byte-aligned
+        // buffer the two differ.
+        let old = self.allocation.as_ptr().addr().wrapping_add(self.top.get());
+        let end = allocation.as_ptr().addr().wrapping_add(max_top);
+        let top = max_top
+            .checked_sub(end.wrapping_sub(old) & (self.top_align.get() - 1))
+            .filter(|&top| top >= self.offset.get());
+        let Some(top) = top else {
+            self.free_storage(allocation);
+            return Err(AllocError);
+        };
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                self.allocation.as_ptr(),
+                allocation.as_ptr(),
+                self.offset.get(),
+            );
+            core::ptr::copy_nonoverlapping(
+                self.allocation.as_ptr().add(self.top.get()),
+                allocation.as_ptr().add(top),
+                top_len,
+            );
+        }
+        Ok((allocation, top))
     }
 
     pub fn can_fit<T>(&self) -> bool {
@@ -38,96 +786,4448 @@ impl Arena {
             .and_then(|(l, _)| self.padding(l))
             .is_some()
     }
+    /// [`Arena::can_fit`] for a layout only known at runtime.
+    pub fn can_fit_layout(&self, layout: Layout) -> bool {
+        self.padding(layout).is_some()
+    }
 
-    fn padding(&self, layout: Layout) -> Option<usize> {
-        let req_size = layout.size();
-        let ptr = self.allocation.as_ptr() as usize + self.offset.get();
-        let padding = (layout.align() - (ptr % layout.align())) % layout.align();
-        let rem_size = (self.allocation.len() - self.offset.get()).checked_sub(padding)?;
-        if rem_size < req_size {
-            return None;
+    /// How many of `layouts`, allocated in order, would fit before the first
+    /// one that does not, without allocating anything.
+    pub fn plan(&self, layouts: impl IntoIterator<Item = Layout>) -> usize {
+        let buffer = self.allocation.prefix(self.top.get());
+        let mut offset = self.offset.get();
+        layouts
+            .into_iter()
+            .take_while(|&layout| {
+                if layout.size() == 0 {
+                    return true;
+                }
+                let align = self.block_align(layout);
+                match padding_for_parts(buffer, offset, layout.size(), align) {
+                    Some(padding) => {
+                        offset += padding + layout.size();
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .count()
+    }
+
+    /// Whether all of `layouts` would fit, allocated in order; the yes/no
+    /// form of [`Arena::plan`].
+    pub fn can_fit_all(&self, layouts: &[Layout]) -> bool {
+        self.plan(layouts.iter().copied()) == layouts.len()
+    }
+
+    /// Like [`Arena::can_fit_layout`], but on a miss tells whether growing
+    /// would help and by how much. The growth is computed for the current
+    /// address; a relocated buffer may need up to `align - 1` more bytes of
+    /// padding.
+    pub fn classify_fit(&self, layout: Layout) -> FitResult {
+        if self.padding(layout).is_some() {
+            return FitResult::Fits;
+        }
+        let end = self
+            .offset
+            .get()
+            .checked_add(self.current_padding(self.block_align(layout)))
+            .and_then(|start| start.checked_add(layout.size()))
+            .filter(|&end| end <= isize::MAX as usize);
+        match end {
+            Some(end) => FitResult::NeedsGrowth(end - self.top.get()),
+            None => FitResult::ImpossibleOverflow,
         }
-        Some(padding)
     }
-}
 
-unsafe impl Allocator for &Arena {
-    fn allocate(&self, layout: std::alloc::Layout) -> Result<NonNull<[u8]>, AllocError> {
-        if layout.size() == 0 {
-            return unsafe {
-                Ok(NonNull::new_unchecked(std::ptr::slice_from_raw_parts_mut(
-                    layout.dangling().as_ptr(),
-                    layout.size(),
-                )))
-            };
+    /// Total size of the backing buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.allocation.len()
+    }
+
+    /// The largest power of two the start of the buffer is a multiple of,
+    /// which is often more than was asked of the allocator. It explains why
+    /// the first over-aligned block sometimes needs no padding.
+    pub fn base_alignment(&self) -> usize {
+        let addr = self.allocation.as_ptr().addr();
+        addr & addr.wrapping_neg()
+    }
+
+    /// The layout the backing buffer was requested with: the capacity and
+    /// the alignment asked for, e.g. by [`Arena::with_capacity_aligned`].
+    /// Borrowed and mapped buffers report an alignment of 1.
+    pub fn backing_layout(&self) -> Layout {
+        self.allocation.layout()
+    }
+
+    /// Like [`Arena::base_alignment`], but for the byte at the current
+    /// offset, where the next block would start before padding.
+    pub fn next_alignment(&self) -> usize {
+        let addr = self
+            .allocation
+            .as_ptr()
+            .addr()
+            .wrapping_add(self.offset.get());
+        addr & addr.wrapping_neg()
+    }
+
+    /// Bytes handed out so far, including alignment padding, counting both
+    /// the upward and the downward region.
+    pub fn used(&self) -> usize {
+        self.offset.get() + (self.allocation.len() - self.top.get())
+    }
+
+    /// Bytes left in the arena.
+    ///
+    /// This is an upper bound: whether a given type fits also depends on the
+    /// alignment padding it needs, which [`Arena::can_fit`] accounts for.
+    pub fn remaining(&self) -> usize {
+        self.top.get() - self.offset.get()
+    }
+
+    /// Whether nothing has been allocated, in either region.
+    pub fn is_empty(&self) -> bool {
+        self.used() == 0
+    }
+
+    /// Whether no free bytes are left, so only zero-sized allocations can
+    /// still succeed.
+    pub fn is_full(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Bytes lost to alignment padding since the last reset, to judge
+    /// whether ordering allocations by alignment would pay off. Blocks left
+    /// behind when a collection outgrew them in the middle of the arena, and
+    /// tails freed by shrinking one there, are counted too. Space given back
+    /// by rewinding is not subtracted.
+    pub fn wasted_padding(&self) -> usize {
+        self.wasted.get()
+    }
+
+    /// All counters at once. `wasted` and `allocations` count since the last
+    /// reset; zero-sized allocations are not counted.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            capacity: self.capacity(),
+            used: self.used(),
+            remaining: self.remaining(),
+            peak: self.peak_used(),
+            wasted: self.wasted_padding(),
+            allocations: self.allocations.get(),
         }
-        let padding = self.padding(layout).ok_or(AllocError)?;
-        let padded_ptr = unsafe {
-            (self.allocation.as_ptr())
-                .add(self.offset.get())
-                .add(padding)
-        };
+    }
 
-        let fat_ptr = unsafe {
-            NonNull::new_unchecked(std::ptr::slice_from_raw_parts_mut(
-                padded_ptr as *mut u8,
-                layout.size(),
-            ))
-        };
-        self.offset.set(self.offset.get() + padding + layout.size());
-        Ok(fat_ptr)
+    /// Validates the arena's bookkeeping, e.g. after each step of a fuzzing
+    /// or property test, and describes the first broken invariant.
+    pub fn check_invariants(&self) -> Result<(), &'static str> {
+        let (offset, top) = (self.offset.get(), self.top.get());
+        if offset > top {
+            return Err("offset is past the start of the downward region");
+        }
+        if top > self.capacity() {
+            return Err("downward region starts past the end of the buffer");
+        }
+        if !self.top_align.get().is_power_of_two() {
+            return Err("downward alignment is not a power of two");
+        }
+        if self.peak.get() < self.used() {
+            return Err("peak is below the current usage");
+        }
+        for markers in [&self.scopes, &self.generations] {
+            let markers = markers.borrow();
+            if markers.windows(2).any(|w| w[0] > w[1]) {
+                return Err("saved offsets are out of order");
+            }
+            if markers.last().is_some_and(|&m| m > offset) {
+                return Err("saved offset is past the current offset");
+            }
+        }
+        #[cfg(feature = "tracking")]
+        if self
+            .record
+            .borrow()
+            .iter()
+            .any(|&(start, layout)| start + layout.size() > offset && start < top)
+        {
+            return Err("recorded block lies in the free space");
+        }
+        Ok(())
     }
 
-    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: std::alloc::Layout) {}
-}
+    /// Checks up front that `additional` bytes are still free, so a batch of
+    /// allocations can fail before it starts rather than halfway through.
+    /// The arena is left unchanged either way.
+    pub fn reserve(&self, additional: usize) -> Result<(), ArenaError> {
+        if additional <= self.remaining() {
+            return Ok(());
+        }
+        if self.allocation.is_empty() {
+            return Err(ArenaError::ZeroCapacity);
+        }
+        Err(ArenaError::CapacityExhausted {
+            requested: additional,
+            remaining: self.remaining(),
+        })
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::Arena;
+    /// Grows the arena, if needed, so that `n` more `T`s allocated one by one
+    /// are sure to fit, e.g. before a bulk insert that should not fail
+    /// partway. The worst-case padding is included.
+    ///
+    /// Like [`Arena::grow_to`] this may relocate the buffer.
+    pub fn reserve_for<T>(&mut self, n: usize) -> Result<(), AllocError> {
+        if n == 0 {
+            return Ok(());
+        }
+        let align = self.block_align(Layout::new::<T>());
+        let needed = core::mem::size_of::<T>()
+            .next_multiple_of(align)
+            .checked_mul(n)
+            .and_then(|bytes| bytes.checked_add(align - 1))
+            .ok_or(AllocError)?;
+        match needed.checked_sub(self.remaining()) {
+            None | Some(0) => Ok(()),
+            Some(missing) => {
+                let capacity = self.capacity().checked_add(missing).ok_or(AllocError)?;
+                self.grow_to(capacity)
+            }
+        }
+    }
 
-    #[test]
-    fn it_works() {
-        let arena = Arena::with_capacity(1024).unwrap();
-        let a = Box::new_in(5.0, &arena);
-        assert_eq!(a.as_ref(), &5.0);
-        let b = Box::new_in(12, &arena);
-        assert_eq!(b.as_ref(), &12)
+    /// Free bytes left for `T`-typed data once the current offset is aligned
+    /// for `T` and the granularity, or 0 if not even the padding fits.
+    pub fn remaining_after_align<T>(&self) -> usize {
+        self.remaining()
+            .saturating_sub(self.current_padding(self.block_align(Layout::new::<T>())))
     }
 
-    #[test]
-    fn it_works_vec() {
-        let arena = Arena::with_capacity(24).unwrap();
-        let mut a: Vec<u8, _> = Vec::new_in(&arena);
-        a.extend(0..10);
-        assert_eq!(&a, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    /// How many `T`s still fit in one slice at the current offset, after its
+    /// alignment padding. Zero-sized types always fit, so this returns
+    /// `usize::MAX` for them.
+    pub fn remaining_count<T>(&self) -> usize {
+        let size = core::mem::size_of::<T>();
+        if size == 0 {
+            return usize::MAX;
+        }
+        let padding = self.current_padding(self.block_align(Layout::new::<T>()));
+        self.remaining().saturating_sub(padding) / size
     }
 
-    #[test]
-    fn test_allignement() {
-        let arena = Arena::with_capacity(24).unwrap();
-        let a = Box::new_in(5u8, &arena);
-        assert_eq!(a.as_ref(), &5);
-        let b = Box::new_in(u128::MAX, &arena);
-        assert_eq!(b.as_ref(), &u128::MAX);
-        assert_eq!(
-            (b.as_ref() as *const u128) as usize % std::mem::align_of_val(b.as_ref()),
-            0
-        );
+    /// Moves `value` into the arena and returns a reference to it that lives
+    /// as long as the borrow of the arena. The value is never dropped.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> Result<&mut T, ArenaError> {
+        self.alloc_with(|| value)
     }
-    #[test]
-    fn test_fit() {
-        let arena = Arena::with_capacity(24).unwrap();
-        assert!(arena.can_fit::<u8>());
-        assert!(!arena.can_fit_slice::<u8>(200));
-        assert!(arena.can_fit_slice::<u8>(24));
-        let mut a: Vec<u8, _> = Vec::new_in(&arena);
-        a.extend(0..24);
-        assert!(!arena.can_fit::<u8>());
+
+    /// Like [`Arena::alloc`], but panics at the caller's location with the
+    /// error, e.g. `arena out of space: needed 48, have 12`, for code that
+    /// treats running out of space as a bug.
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_or_panic<T>(&self, value: T) -> &mut T {
+        match self.alloc(value) {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
     }
 
-    #[test]
-    fn test_zero_sized() {
-        let arena = Arena::with_capacity(8).unwrap();
-        let _ = Box::new_in((), &arena);
+    /// Reserves a slot for a `T` and then writes `f()` straight into it,
+    /// which lets large values be built in place instead of being moved in.
+    ///
+    /// If `f` panics the slot has already been bumped past and stays used,
+    /// even though nothing was written to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_with<T>(&self, f: impl FnOnce() -> T) -> Result<&mut T, ArenaError> {
+        Ok(self.alloc_uninit::<T>()?.write(f()))
+    }
+
+    /// Reserves an uninitialized slot for a `T`, to be filled in place.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_uninit<T>(&self) -> Result<&mut MaybeUninit<T>, ArenaError> {
+        let ptr = self.bump(Layout::new::<T>())?.cast::<MaybeUninit<T>>();
+        Ok(unsafe { &mut *ptr.as_ptr() })
+    }
+
+    /// Reserves `n` uninitialized `T`s in one slice.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_uninit_slice<T>(&self, n: usize) -> Result<&mut [MaybeUninit<T>], ArenaError> {
+        let ptr = self
+            .bump(Arena::array_layout::<T>(n)?)?
+            .cast::<MaybeUninit<T>>();
+        Ok(unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr(), n) })
+    }
+
+    /// Reserves `N` uninitialized `T`s as an array, keeping the length in
+    /// the type where [`Arena::alloc_uninit_slice`] would lose it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_array<T, const N: usize>(&self) -> Result<&mut [MaybeUninit<T>; N], ArenaError> {
+        let ptr = self
+            .bump(Layout::new::<[T; N]>())?
+            .cast::<[MaybeUninit<T>; N]>();
+        Ok(unsafe { &mut *ptr.as_ptr() })
+    }
+
+    /// Reserves `n` uninitialized bytes with no alignment, e.g. as the target
+    /// of a read or of decompression.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_bytes(&self, n: usize) -> Result<&mut [MaybeUninit<u8>], ArenaError> {
+        self.alloc_uninit_slice::<u8>(n)
+    }
+
+    /// Reads exactly `n` bytes from `src` straight into the arena. The block
+    /// is zeroed first, since `Read` may look at the buffer it is given. A
+    /// full arena is reported as [`std::io::ErrorKind::OutOfMemory`]; if the
+    /// read fails, the block is given back when it is still the last one.
+    #[cfg(feature = "std")]
+    #[allow(clippy::mut_from_ref)]
+    pub fn read_from(&self, src: &mut impl std::io::Read, n: usize) -> std::io::Result<&mut [u8]> {
+        let bytes = self
+            .alloc_bytes(n)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::OutOfMemory, e))?;
+        let ptr = bytes.as_mut_ptr().cast::<u8>();
+        let buf = unsafe {
+            ptr.write_bytes(0, n);
+            core::slice::from_raw_parts_mut(ptr, n)
+        };
+        match src.read_exact(buf) {
+            Ok(()) => Ok(buf),
+            Err(e) => {
+                unsafe { self.pop(ptr, Layout::array::<u8>(n).unwrap()) };
+                Err(e)
+            }
+        }
+    }
+
+    /// Reserves `layout` for an unsized `T` with pointer `metadata`, e.g. a
+    /// struct ending in a slice, and returns it as a pointer since the value
+    /// is not initialized yet. `layout` must be large enough for a `T` with
+    /// that metadata before the pointer is dereferenced.
+    pub fn alloc_unsized<T: ?Sized>(
+        &self,
+        layout: Layout,
+        metadata: <T as Pointee>::Metadata,
+    ) -> Result<NonNull<T>, ArenaError> {
+        let ptr = self.bump(layout)?.cast::<u8>();
+        Ok(NonNull::from_raw_parts(ptr, metadata))
+    }
+
+    /// Like [`Arena::alloc`], also returning the value's offset for
+    /// [`Arena::at_offset`]. For zero-sized types, which take no space, this
+    /// is the current position.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_tracked<T>(&self, value: T) -> Result<(usize, &mut T), ArenaError> {
+        let value = self.alloc(value)?;
+        let offset = match core::mem::size_of::<T>() {
+            0 => self.position(),
+            _ => (value as *mut T as usize) - self.base_ptr() as usize,
+        };
+        Ok((offset, value))
+    }
+
+    /// Moves `value` into a `Box` allocated from the arena. Unlike
+    /// `Box::new_in`, running out of space is an error rather than an abort,
+    /// and the value is dropped with the box.
+    pub fn boxed<T>(&self, value: T) -> Result<Box<T, &Self>, ArenaError> {
+        let value: *mut T = self.alloc(value)?;
+        // The box deallocates its block on drop unless `T` is zero-sized.
+        if core::mem::size_of::<T>() != 0 {
+            self.live.set(self.live.get() + 1);
+        }
+        Ok(unsafe { Box::from_raw_in(value, self) })
+    }
+
+    /// Allocates a block for `layout`, exactly like [`Allocator::allocate`]
+    /// but without going through `&Arena`. Like any block from the
+    /// allocator, it counts as live for [`Arena::reset_checked`] until it is
+    /// handed back with [`Allocator::deallocate`].
+    #[inline]
+    pub fn allocate_raw(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.bump(layout)?;
+        self.live.set(self.live.get() + 1);
+        // `Allocator` hands out `[u8]` that the caller must not read before
+        // writing, which is the contract `MaybeUninit` spells out.
+        Ok(NonNull::slice_from_raw_parts(ptr.cast(), ptr.len()))
+    }
+
+    /// An empty `Vec` with room for `capacity` elements reserved in the
+    /// arena, or an error instead of the abort `Vec::with_capacity_in` would
+    /// hit. Pushing beyond the capacity grows in place while the vector's
+    /// buffer is the last block.
+    pub fn alloc_vec<T>(&self, capacity: usize) -> Result<Vec<T, &Self>, ArenaError> {
+        let layout = Arena::array_layout::<T>(capacity)?;
+        let ptr = self.bump(layout)?.cast::<T>();
+        // Like a box, the vector deallocates any non-empty buffer on drop.
+        if layout.size() != 0 {
+            self.live.set(self.live.get() + 1);
+        }
+        Ok(unsafe { Vec::from_raw_parts_in(ptr.as_ptr(), 0, capacity, self) })
+    }
+
+    /// An empty `hashbrown` map whose table lives in the arena. Nothing is
+    /// allocated until the first insert.
+    #[cfg(feature = "hashbrown")]
+    pub fn hash_map<K, V, S: Default>(&self) -> hashbrown::HashMap<K, V, S, &Self> {
+        hashbrown::HashMap::with_hasher_in(S::default(), self)
+    }
+
+    /// Allocates `T::default()` in the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_default<T: Default>(&self) -> Result<&mut T, ArenaError> {
+        self.alloc(T::default())
+    }
+
+    /// Copies `src` into the arena and returns the copy.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> Result<&mut [T], ArenaError> {
+        if src.is_empty() {
+            return Ok(&mut []);
+        }
+        let ptr = self.bump(Arena::array_layout::<T>(src.len())?)?.cast::<T>();
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+            Ok(core::slice::from_raw_parts_mut(ptr.as_ptr(), src.len()))
+        }
+    }
+
+    /// Copies as much of `src` as fits and returns the copied prefix, which
+    /// may be empty; its length tells how many elements were written.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy_saturating<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let n = src.len().min(self.remaining_count::<T>());
+        self.alloc_slice_copy(&src[..n]).unwrap_or_default()
+    }
+
+    /// Reserves room for `n` uninitialized `T`s, or returns `None` if they do
+    /// not fit. Unlike the `alloc*` methods this builds no error, for hot
+    /// paths that only need to branch.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc_uninit_slice<T>(&self, n: usize) -> Option<&mut [MaybeUninit<T>]> {
+        let (layout, _) = Layout::new::<T>().repeat(n).ok()?;
+        let ptr = self.try_bump(layout)?.cast::<MaybeUninit<T>>();
+        Some(unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr(), n) })
+    }
+
+    /// Copies `s` into the arena, e.g. to intern strings without a `String`
+    /// allocation each.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, s: &str) -> Result<&mut str, ArenaError> {
+        let bytes = self.alloc_slice_copy(s.as_bytes())?;
+        Ok(unsafe { core::str::from_utf8_unchecked_mut(bytes) })
+    }
+
+    /// Moves the items of `iter` into one contiguous slice. The lower bound of
+    /// its size hint is checked up front so a batch that cannot fit fails
+    /// before consuming anything; past that, items are bumped one by one
+    /// until the arena runs out.
+    ///
+    /// On failure the items written so far are dropped and their space is
+    /// given back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the iterator itself allocates from this arena, which would
+    /// break the slice apart.
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_from_iter<T>(
+        &self,
+        iter: impl IntoIterator<Item = T>,
+    ) -> Result<&mut [T], ArenaError> {
+        let iter = iter.into_iter();
+        let lower = Arena::array_layout::<T>(iter.size_hint().0)?;
+        if self.padding(lower).is_none() {
+            return Err(self.fit_error(lower));
+        }
+        // Everything the aborted slice counted is rolled back on failure, as
+        // nothing of it was handed out.
+        let offset = self.offset.get();
+        let (peak, cycle_peak) = (self.peak.get(), self.cycle_peak.get());
+        let (wasted, allocations, live) =
+            (self.wasted.get(), self.allocations.get(), self.live.get());
+        let lifetime_bytes = self.lifetime_bytes.get();
+        let mut start = NonNull::<T>::dangling();
+        let mut len = 0;
+        let size = core::mem::size_of::<T>();
+        for value in iter {
+            // Only the first item can need padding; the rest extend its block.
+            let slot = match len {
+                _ if size == 0 => Ok(NonNull::dangling()),
+                0 => self.bump(Layout::new::<T>()).map(NonNull::cast),
+                _ => match Arena::array_layout::<T>(len + 1)
+                    .and_then(|layout| self.grow_last(start.as_ptr().cast(), len * size, layout))
+                {
+                    Err(ArenaError::NotLastBlock) => {
+                        panic!("iterator allocated from the arena it is collected into")
+                    }
+                    result => result.map(|()| unsafe { start.add(len) }),
+                },
+            };
+            let ptr = match slot {
+                Ok(ptr) => ptr,
+                Err(err) => {
+                    unsafe {
+                        core::ptr::slice_from_raw_parts_mut(start.as_ptr(), len).drop_in_place();
+                    }
+                    self.offset.set(offset);
+                    self.peak.set(peak);
+                    self.cycle_peak.set(cycle_peak);
+                    self.wasted.set(wasted);
+                    self.allocations.set(allocations);
+                    self.live.set(live);
+                    self.lifetime_bytes.set(lifetime_bytes);
+                    self.drop_markers_above(offset);
+                    self.untrack();
+                    return Err(err);
+                }
+            };
+            if len == 0 {
+                start = ptr;
+            }
+            unsafe { ptr.as_ptr().write(value) };
+            len += 1;
+        }
+        Ok(unsafe { core::slice::from_raw_parts_mut(start.as_ptr(), len) })
+    }
+
+    /// Allocates `n` `T`s, the `i`th being `f(i)`, without a temporary
+    /// collection.
+    ///
+    /// If `f` panics, the elements written so far are dropped; their space
+    /// stays allocated until the next reset.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_with<T>(
+        &self,
+        n: usize,
+        mut f: impl FnMut(usize) -> T,
+    ) -> Result<&mut [T], ArenaError> {
+        /// Drops the initialized prefix if `f` unwinds.
+        struct Prefix<T> {
+            start: *mut T,
+            len: usize,
+        }
+
+        impl<T> Drop for Prefix<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    core::ptr::slice_from_raw_parts_mut(self.start, self.len).drop_in_place()
+                };
+            }
+        }
+
+        let start = self
+            .bump(Arena::array_layout::<T>(n)?)?
+            .cast::<T>()
+            .as_ptr();
+        let mut prefix = Prefix { start, len: 0 };
+        for i in 0..n {
+            unsafe { start.add(i).write(f(i)) };
+            prefix.len += 1;
+        }
+        core::mem::forget(prefix);
+        Ok(unsafe { core::slice::from_raw_parts_mut(start, n) })
+    }
+
+    /// Clones `src` into the arena, for element types that are not `Copy`.
+    ///
+    /// If a `clone` panics, the clones made so far are dropped, as with
+    /// [`Arena::alloc_slice_with`].
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_clone<T: Clone>(&self, src: &[T]) -> Result<&mut [T], ArenaError> {
+        self.alloc_slice_with(src.len(), |i| src[i].clone())
+    }
+
+    /// Allocates `n` `T::default()`s starting on an `align` byte boundary,
+    /// e.g. a cache-line aligned buffer. An `align` below that of `T` is
+    /// raised to it. `align` must be a power of two.
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_aligned_slice<T: Default>(
+        &self,
+        n: usize,
+        align: usize,
+    ) -> Result<&mut [T], ArenaError> {
+        debug_assert!(align.is_power_of_two(), "alignment must be a power of two");
+        let layout = Arena::array_layout::<T>(n)?
+            .align_to(align)
+            .map_err(|_| ArenaError::LayoutOverflow)?;
+        let ptr = self.bump(layout)?.cast::<T>();
+        unsafe {
+            for i in 0..n {
+                ptr.as_ptr().add(i).write(T::default());
+            }
+            Ok(core::slice::from_raw_parts_mut(ptr.as_ptr(), n))
+        }
+    }
+
+    /// Allocates `len` zeroed bytes starting on a page boundary, e.g. for a
+    /// sub-buffer handed to `mmap` or `mprotect`. The arena only needs room
+    /// for the padding to the next boundary, not a page-aligned base.
+    #[cfg(all(feature = "mmap", unix))]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_page_aligned_slice(&self, len: usize) -> Result<&mut [u8], ArenaError> {
+        self.alloc_aligned_slice(len, mapped::page_size())
+    }
+
+    /// Allocates a `rows` by `cols` grid of `T::default()` in which every row
+    /// starts on a `row_align` byte boundary, e.g. for SIMD over image rows.
+    /// Returns the grid with its stride in elements: row `r` starts at
+    /// `r * stride`. `row_align` must be a power of two.
+    ///
+    /// The stride is the smallest whole number of `T`s that is at least
+    /// `cols` and keeps rows aligned; the padding after the last row is not
+    /// allocated.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_rows<T: Default>(
+        &self,
+        rows: usize,
+        cols: usize,
+        row_align: usize,
+    ) -> Result<(&mut [T], usize), ArenaError> {
+        debug_assert!(
+            row_align.is_power_of_two(),
+            "alignment must be a power of two"
+        );
+        let size = core::mem::size_of::<T>();
+        let stride = match size {
+            0 => cols,
+            _ => {
+                // Rows stay aligned when the stride is a multiple of the part
+                // of `row_align` that `size` does not already cover.
+                let step = row_align >> size.trailing_zeros().min(row_align.trailing_zeros());
+                cols.checked_next_multiple_of(step)
+                    .ok_or(ArenaError::LayoutOverflow)?
+            }
+        };
+        let len = match rows {
+            0 => 0,
+            _ => (rows - 1)
+                .checked_mul(stride)
+                .and_then(|len| len.checked_add(cols))
+                .ok_or(ArenaError::LayoutOverflow)?,
+        };
+        let layout = Arena::array_layout::<T>(len)?
+            .align_to(row_align)
+            .map_err(|_| ArenaError::LayoutOverflow)?;
+        let ptr = self.bump(layout)?.cast::<T>();
+        unsafe {
+            for i in 0..len {
+                ptr.as_ptr().add(i).write(T::default());
+            }
+            Ok((core::slice::from_raw_parts_mut(ptr.as_ptr(), len), stride))
+        }
+    }
+
+    /// Padding bytes a block with `layout` would need at the current offset,
+    /// or `None` if it would not fit. This is the exact value the next
+    /// allocation uses, so batches can be costed up front.
+    pub fn padding_for(&self, layout: Layout) -> Option<usize> {
+        self.padding(layout)
+    }
+
+    /// Padding bytes allocating a `T` right now would cost, whether or not
+    /// it fits, e.g. to flag allocation orders that interleave alignments
+    /// badly. Zero-sized types never need any.
+    pub fn would_waste<T>(&self) -> usize {
+        match core::mem::size_of::<T>() {
+            0 => 0,
+            _ => self.current_padding(self.block_align(Layout::new::<T>())),
+        }
+    }
+
+    /// Start of the backing buffer, for writing into it.
+    fn base_ptr(&self) -> *mut u8 {
+        self.allocation.as_ptr()
+    }
+
+    /// Bytes needed to align the current offset to `align`, whether or not
+    /// anything fits after them.
+    fn current_padding(&self, align: usize) -> usize {
+        align_padding(
+            self.allocation
+                .as_ptr()
+                .addr()
+                .wrapping_add(self.offset.get()),
+            align,
+        )
+    }
+
+    #[inline]
+    fn padding(&self, layout: Layout) -> Option<usize> {
+        let align = self.block_align(layout);
+        // Byte-aligned blocks never need padding, so packing bytes skips the
+        // address arithmetic.
+        if align == 1 {
+            return (layout.size() <= self.remaining()).then_some(0);
+        }
+        padding_for_parts(
+            self.allocation.prefix(self.top.get()),
+            self.offset.get(),
+            layout.size(),
+            align,
+        )
+    }
+
+    /// The alignment a block for `layout` actually gets, raised to the
+    /// granularity.
+    fn block_align(&self, layout: Layout) -> usize {
+        layout.align().max(self.granularity)
+    }
+
+    /// Advances the offset to `offset`, raising the high-water mark.
+    fn bump_to(&self, offset: usize) {
+        self.offset.set(offset);
+        let (start, end) = self.zeroed.get();
+        self.zeroed.set((start.max(offset), end));
+        self.peak.set(self.peak.get().max(self.used()));
+        self.cycle_peak.set(self.cycle_peak.get().max(self.used()));
+    }
+
+    /// Zeroes `len` bytes of alignment padding at `start`, so that a fully
+    /// written region can be read back as bytes.
+    #[inline]
+    fn zero_padding(&self, start: usize, len: usize) {
+        if len != 0 {
+            unsafe { self.base_ptr().add(start).write_bytes(0, len) };
+        }
+    }
+
+    /// Marks every [`NodeRef`] handed out so far as stale if one of them
+    /// reaches past `offset`, where the upward region was just cut back to.
+    fn forget_nodes_from(&self, offset: usize) {
+        if offset < self.nodes_end.get() {
+            self.epoch.set(self.epoch.get().wrapping_add(1));
+            self.nodes_end.set(0);
+        }
+    }
+
+    /// Adds `bytes` to [`Arena::lifetime_allocated`].
+    fn count_lifetime(&self, bytes: usize) {
+        self.lifetime_bytes
+            .set(self.lifetime_bytes.get().saturating_add(bytes as u64));
+    }
+
+    /// Bytes handed out since the arena was created, padding included.
+    /// Unlike [`Arena::used`] resets do not lower it, so sampled over time
+    /// it gives the allocation throughput.
+    pub fn lifetime_allocated(&self) -> u64 {
+        self.lifetime_bytes.get()
+    }
+
+    /// The highest offset the arena has reached since it was created. Unlike
+    /// [`Arena::used`] this survives resets, which helps sizing the next run.
+    pub fn peak_used(&self) -> usize {
+        self.peak.get()
+    }
+
+    /// Bumps the offset past a block for `layout`, reporting why it failed
+    /// if it does not fit. The block may hold stale or never written bytes.
+    #[inline]
+    fn bump(&self, layout: Layout) -> Result<NonNull<[MaybeUninit<u8>]>, ArenaError> {
+        self.try_bump(layout).ok_or_else(|| self.fit_error(layout))
+    }
+
+    #[inline]
+    fn try_bump(&self, layout: Layout) -> Option<NonNull<[MaybeUninit<u8>]>> {
+        if layout.size() == 0 {
+            return Some(NonNull::slice_from_raw_parts(
+                layout.dangling_ptr().cast(),
+                0,
+            ));
+        }
+        // The padding that decided the fit is the one used for the pointer,
+        // so `can_fit` and `allocate` can never disagree for a layout.
+        let padding = self.padding(layout)?;
+        // `padding` already bounds the block by the capacity; checking again
+        // here keeps a bad offset from ever being stored, and nothing is
+        // updated until the end is known.
+        let start = self.offset.get().checked_add(padding)?;
+        let end = start.checked_add(layout.size())?;
+        let padded_ptr = unsafe { self.allocation.as_ptr().add(start) };
+        self.zero_padding(self.offset.get(), padding);
+
+        let fat_ptr = unsafe {
+            NonNull::new_unchecked(core::ptr::slice_from_raw_parts_mut(
+                padded_ptr.cast::<MaybeUninit<u8>>(),
+                layout.size(),
+            ))
+        };
+        self.wasted.set(self.wasted.get() + padding);
+        self.allocations.set(self.allocations.get() + 1);
+        self.count_lifetime(padding + layout.size());
+        self.track(start, layout);
+        self.bump_to(end);
+        debug_assert!(
+            padded_ptr.cast::<u8>().addr() % layout.align() == 0,
+            "allocation is misaligned"
+        );
+        Some(fat_ptr)
+    }
+
+    // Only reached once an allocation failed, so keep it out of the inlined
+    // fast path.
+    #[cold]
+    fn fit_error(&self, layout: Layout) -> ArenaError {
+        if self.allocation.is_empty() {
+            return ArenaError::ZeroCapacity;
+        }
+        let padding = self.current_padding(self.block_align(layout));
+        match padding
+            .checked_add(layout.size())
+            .filter(|needed| self.offset.get().checked_add(*needed).is_some())
+        {
+            Some(requested) => ArenaError::CapacityExhausted {
+                requested,
+                remaining: self.remaining(),
+            },
+            None => ArenaError::LayoutOverflow,
+        }
+    }
+
+    /// Advances the offset so the next allocation starts on an `align` byte
+    /// boundary, whatever its own alignment. `align` must be a power of two.
+    #[track_caller]
+    pub fn align_to(&self, align: usize) -> Result<(), ArenaError> {
+        debug_assert!(align.is_power_of_two(), "alignment must be a power of two");
+        let padding = self.current_padding(align);
+        if padding > self.remaining() {
+            return Err(ArenaError::CapacityExhausted {
+                requested: padding,
+                remaining: self.remaining(),
+            });
+        }
+        self.wasted.set(self.wasted.get() + padding);
+        self.zero_padding(self.offset.get(), padding);
+        self.bump_to(self.offset.get() + padding);
+        Ok(())
+    }
+
+    /// Allocates from the end of the buffer downward, so one arena can hold
+    /// two independent regions that grow towards each other. Fails once the
+    /// block would cross into the upward region.
+    ///
+    /// Downward blocks are only reclaimed by [`Arena::reset`]; rewinding and
+    /// `deallocate` only affect the upward region.
+    pub fn allocate_down(&self, layout: Layout) -> Result<NonNull<[u8]>, ArenaError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(layout.dangling_ptr(), 0));
+        }
+        let base = self.allocation.as_ptr() as usize;
+        let start = self
+            .top
+            .get()
+            .checked_sub(layout.size())
+            .and_then(|start| {
+                start.checked_sub(base.wrapping_add(start) & (self.block_align(layout) - 1))
+            })
+            .filter(|&start| start >= self.offset.get())
+            .ok_or_else(|| self.fit_error(layout))?;
+        self.wasted
+            .set(self.wasted.get() + (self.top.get() - start - layout.size()));
+        self.zero_padding(
+            start + layout.size(),
+            self.top.get() - start - layout.size(),
+        );
+        self.count_lifetime(self.top.get() - start);
+        self.top.set(start);
+        self.allocations.set(self.allocations.get() + 1);
+        self.track(start, layout);
+        let (zeroed_start, zeroed_end) = self.zeroed.get();
+        self.zeroed.set((zeroed_start, zeroed_end.min(start)));
+        self.top_align.set(self.top_align.get().max(layout.align()));
+        self.peak.set(self.peak.get().max(self.used()));
+        self.cycle_peak.set(self.cycle_peak.get().max(self.used()));
+        let ptr = unsafe { NonNull::new_unchecked(self.base_ptr().add(start)) };
+        debug_assert!(
+            ptr.addr().get() % layout.align() == 0,
+            "allocation is misaligned"
+        );
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    /// Moves `value` into the downward region; see [`Arena::allocate_down`].
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_down<T>(&self, value: T) -> Result<&mut T, ArenaError> {
+        let ptr = self.allocate_down(Layout::new::<T>())?.cast::<T>();
+        unsafe {
+            ptr.as_ptr().write(value);
+            Ok(&mut *ptr.as_ptr())
+        }
+    }
+
+    /// The bytes handed out so far, e.g. for snapshotting the arena. This is
+    /// the upward region only. Padding between blocks is zeroed as it is
+    /// skipped.
+    ///
+    /// Allocating only needs `&self`, so a shared view could alias a live
+    /// `&mut` into the arena. Taking `&mut self` rules that out.
+    ///
+    /// # Safety
+    ///
+    /// Every block in the upward region must be fully initialized: not
+    /// reserved and left unwritten, e.g. by [`Arena::alloc_uninit`] or the
+    /// spare capacity of a `Vec`, and not holding a value with padding
+    /// inside it. Reading uninitialized bytes as `u8` is undefined behavior.
+    pub unsafe fn used_bytes(&mut self) -> &[u8] {
+        unsafe { self.allocation.slice(0..self.offset.get()) }
+    }
+
+    /// Writes a hex view of [`Arena::used_bytes`] to `w`, sixteen bytes per
+    /// line as `offset: hex | ascii` like `xxd`, e.g. to inspect what a
+    /// serializer wrote. Takes `&mut self` for the same reason.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Arena::used_bytes`].
+    #[cfg(feature = "std")]
+    pub unsafe fn dump(&mut self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        for (line, chunk) in unsafe { self.used_bytes() }.chunks(16).enumerate() {
+            write!(w, "{:08x}:", line * 16)?;
+            for byte in chunk {
+                write!(w, " {byte:02x}")?;
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            writeln!(w, "{:pad$} | {ascii}", "", pad = (16 - chunk.len()) * 3)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `value` over the free space without allocating it, e.g. `0xAA`
+    /// to make reads of uninitialized memory stand out in tests.
+    pub fn fill_remaining(&self, value: u8) {
+        self.zeroed.set((0, 0));
+        unsafe {
+            self.base_ptr()
+                .add(self.offset.get())
+                .write_bytes(value, self.remaining());
+        }
+    }
+
+    /// Touches every page of the free space so the OS commits it now rather
+    /// than on first use, e.g. before a latency-sensitive phase. Writes only
+    /// zeros to free bytes, so zeroed space stays zeroed.
+    pub fn prefault(&self) {
+        let (start, end) = (self.offset.get(), self.top.get());
+        let base = self.base_ptr();
+        let page = page_size();
+        let mut offset = start;
+        while offset < end {
+            unsafe { base.add(offset).write_volatile(0) };
+            // Step to the start of the next page.
+            offset += 1;
+            offset += align_padding(base as usize + offset, page);
+        }
+    }
+
+    /// Appends a copy of the upward region's used bytes to `dst` at its
+    /// current offset, e.g. to move the contents into an arena sized to fit.
+    ///
+    /// Like [`Clone`], pointers stored in the bytes are copied as-is, so this
+    /// is only meaningful for plain data or data linked by offsets. The bytes
+    /// are read through a raw pointer, so no `&mut` into `self` may be
+    /// written to during the call.
+    pub fn copy_into<B: Allocator>(&self, dst: &mut Arena<'_, B>) -> Result<(), ArenaError> {
+        self.copy_used_to(dst).map(drop)
+    }
+
+    /// Appends a copy of `other`'s used bytes, e.g. to merge shards built in
+    /// separate arenas, and returns the offset they start at so offsets
+    /// stored in them can be fixed up. The same caveats as for
+    /// [`Arena::copy_into`] apply.
+    pub fn append<B: Allocator>(&mut self, other: &Arena<'_, B>) -> Result<usize, ArenaError> {
+        other.copy_used_to(self)
+    }
+
+    /// [`Arena::copy_into`], returning the offset of the copy in `dst`.
+    fn copy_used_to<B: Allocator>(&self, dst: &mut Arena<'_, B>) -> Result<usize, ArenaError> {
+        let len = self.offset.get();
+        let copy = dst.bump(Arena::array_layout::<u8>(len)?)?.cast::<u8>();
+        unsafe { core::ptr::copy_nonoverlapping(self.allocation.as_ptr(), copy.as_ptr(), len) };
+        Ok(copy.as_ptr() as usize - dst.allocation.as_ptr() as usize)
+    }
+
+    /// Whether the block of `layout` at `ptr` is the most recent upward
+    /// allocation, the only one [`Arena::pop`] can give back.
+    pub fn is_last_allocation(&self, ptr: *const u8, layout: Layout) -> bool {
+        self.is_last_block(ptr, layout.size())
+    }
+
+    /// Gives back the block of `layout` at `ptr` if it is the most recent
+    /// allocation, and does nothing otherwise, like freeing through the
+    /// [`Allocator`] impl. Its leading padding is not reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// The block must not be used once this returns.
+    pub unsafe fn pop(&self, ptr: *const u8, layout: Layout) {
+        if self.is_last_allocation(ptr, layout) {
+            unsafe { self.rewind(Marker(self.offset.get() - layout.size())) };
+        }
+    }
+
+    /// The half-open address range of the backing buffer, e.g. for an
+    /// external tool to check whether it holds a pointer.
+    pub fn as_ptr_range(&self) -> core::ops::Range<*const u8> {
+        let start = self.allocation.as_ptr().cast_const();
+        start..start.wrapping_add(self.allocation.len())
+    }
+
+    /// Whether `ptr` points into this arena's backing buffer.
+    pub fn owns(&self, ptr: *const u8) -> bool {
+        self.as_ptr_range().contains(&ptr)
+    }
+
+    /// Offset of `ptr` from the start of the buffer, or `None` if the arena
+    /// does not own it. Unlike the pointer, the offset survives relocation
+    /// and persisting the buffer.
+    pub fn offset_of(&self, ptr: *const u8) -> Option<usize> {
+        self.owns(ptr)
+            .then(|| ptr as usize - self.allocation.as_ptr() as usize)
+    }
+
+    /// Turns an offset from [`Arena::offset_of`] back into a pointer.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must lie within the buffer. Dereferencing the result
+    /// additionally requires an initialized, suitably aligned `T` there.
+    pub unsafe fn at_offset<T>(&self, offset: usize) -> *mut T {
+        debug_assert!(offset <= self.capacity(), "offset outside the arena");
+        unsafe { self.base_ptr().add(offset).cast() }
+    }
+
+    /// The current bump offset, i.e. where the next unpadded allocation
+    /// starts. Pair it with [`Arena::slice_at`] to patch earlier bytes.
+    pub fn position(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// The bump offset that [`Arena::set_offset`] moves; the same as
+    /// [`Arena::position`].
+    pub fn offset(&self) -> usize {
+        self.position()
+    }
+
+    /// Moves the bump offset to `offset`, the most primitive form of
+    /// [`Arena::checkpoint`] and [`Arena::rewind`]; read it back with
+    /// [`Arena::offset`].
+    ///
+    /// # Safety
+    ///
+    /// `offset` must not reach into the downward region. Moving backward
+    /// invalidates every allocation past `offset`, like a rewind. Moving
+    /// forward hands the skipped bytes to the caller as allocated, but
+    /// they are not initialized: reading them before writing them is
+    /// undefined behavior.
+    pub unsafe fn set_offset(&self, offset: usize) {
+        debug_assert!(offset <= self.top.get(), "offset outside the free space");
+        let current = self.offset.get();
+        if offset >= current {
+            self.bump_to(offset);
+        } else {
+            self.poison(offset, current);
+            self.offset.set(offset);
+            self.forget_nodes_from(offset);
+            self.drop_markers_above(offset);
+            self.untrack();
+        }
+    }
+
+    /// Re-views `len` previously allocated bytes starting at `offset`, e.g.
+    /// to patch a header once the records after it are written.
+    ///
+    /// # Safety
+    ///
+    /// `offset..offset + len` must lie within the upward used region, and no
+    /// other reference to those bytes may be alive while the slice is used.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn slice_at(&self, offset: usize, len: usize) -> &mut [u8] {
+        debug_assert!(
+            offset
+                .checked_add(len)
+                .is_some_and(|end| end <= self.offset.get()),
+            "slice outside the used region"
+        );
+        unsafe { core::slice::from_raw_parts_mut(self.base_ptr().add(offset), len) }
+    }
+
+    /// Moves `value` into the arena and returns its offset rather than a
+    /// reference; see [`NodeRef`].
+    pub fn alloc_node<T>(&self, value: T) -> Result<NodeRef<T>, ArenaError> {
+        let (offset, _) = self.alloc_tracked(value)?;
+        // A zero-sized value takes no space, so no rewind can overwrite it.
+        if core::mem::size_of::<T>() != 0 {
+            self.nodes_end
+                .set(self.nodes_end.get().max(offset + core::mem::size_of::<T>()));
+        }
+        Ok(NodeRef(offset, self.epoch.get(), PhantomData))
+    }
+
+    /// The value behind `node`, or `None` if it does not lie within the
+    /// upward used region, the arena was reset or rewound over any node since
+    /// it was allocated, or relocating left it misaligned.
+    ///
+    /// Bounds are checked but provenance is not: `node` must come from this
+    /// arena. Like a reset, misuse is unsafe in spirit.
+    pub fn get<T>(&mut self, node: NodeRef<T>) -> Option<&T> {
+        self.node_ptr(node).map(|ptr| unsafe { &*ptr })
+    }
+
+    /// Mutable version of [`Arena::get`].
+    pub fn get_mut<T>(&mut self, node: NodeRef<T>) -> Option<&mut T> {
+        self.node_ptr(node).map(|ptr| unsafe { &mut *ptr })
+    }
+
+    fn node_ptr<T>(&self, node: NodeRef<T>) -> Option<*mut T> {
+        if node.1 != self.epoch.get() {
+            return None;
+        }
+        // Zero-sized values are not in the buffer at all.
+        if core::mem::size_of::<T>() == 0 {
+            return Some(NonNull::dangling().as_ptr());
+        }
+        let in_bounds = node
+            .0
+            .checked_add(core::mem::size_of::<T>())
+            .is_some_and(|end| end <= self.offset.get());
+        if !in_bounds {
+            return None;
+        }
+        let ptr = unsafe { self.base_ptr().add(node.0) }.cast::<T>();
+        ptr.is_aligned().then_some(ptr)
+    }
+
+    /// The body of `Allocator::deallocate`, also used when `grow` or
+    /// `shrink` move a block.
+    unsafe fn free_block(&self, ptr: NonNull<u8>, layout: Layout) {
+        // Blocks freed after an unchecked reset were no longer counted.
+        self.live.set(self.live.get().saturating_sub(1));
+        // Only the most recent block can be given back: move the offset back
+        // to its start. Its leading padding is not reclaimed, and neither is
+        // a block a scope or generation was started after.
+        let start = self.offset.get().wrapping_sub(layout.size());
+        if self.is_last_block(ptr.as_ptr(), layout.size()) && !self.marker_above(start) {
+            self.offset.set(start);
+            self.untrack();
+        }
+    }
+
+    /// Whether the `size` bytes at `ptr` end exactly at the bump position,
+    /// i.e. they are the most recent allocation.
+    fn is_last_block(&self, ptr: *const u8, size: usize) -> bool {
+        size != 0
+            && self.owns(ptr)
+            && ptr.wrapping_add(size) == self.allocation.as_ptr().wrapping_add(self.offset.get())
+    }
+
+    /// Grows the last block, `old_size` bytes at `ptr`, in place to cover
+    /// `new_layout`, whose alignment `ptr` must already satisfy.
+    fn grow_last(
+        &self,
+        ptr: *const u8,
+        old_size: usize,
+        new_layout: Layout,
+    ) -> Result<(), ArenaError> {
+        if !self.is_last_block(ptr, old_size) {
+            return Err(ArenaError::NotLastBlock);
+        }
+        let start = self.offset.get() - old_size;
+        let additional = new_layout.size() - old_size;
+        if additional > self.remaining() {
+            return Err(ArenaError::CapacityExhausted {
+                requested: additional,
+                remaining: self.remaining(),
+            });
+        }
+        self.bump_to(start + new_layout.size());
+        self.retrack(start, new_layout);
+        Ok(())
+    }
+
+    /// With the `poison` feature, overwrites the reclaimed bytes
+    /// `start..end` with `0xDE` so stale reads after a reset or rewind stand
+    /// out.
+    #[cfg_attr(not(feature = "poison"), allow(unused_variables))]
+    fn poison(&self, start: usize, end: usize) {
+        #[cfg(feature = "poison")]
+        unsafe {
+            self.base_ptr().add(start).write_bytes(0xDE, end - start)
+        };
+    }
+
+    /// Records a new block for [`Arena::allocations_iter`].
+    #[cfg_attr(not(feature = "tracking"), allow(unused_variables))]
+    fn track(&self, start: usize, layout: Layout) {
+        #[cfg(feature = "tracking")]
+        self.record.borrow_mut().push((start, layout));
+    }
+
+    /// Drops every offset saved by [`Arena::push_scope`] or
+    /// [`Arena::new_generation`] past `offset`, where the upward region was
+    /// just cut back to, so a later pop cannot move the offset forward over
+    /// freed bytes. Both stacks are sorted, so only their tops are checked.
+    fn drop_markers_above(&self, offset: usize) {
+        for markers in [&self.scopes, &self.generations] {
+            let mut markers = markers.borrow_mut();
+            while markers.last().is_some_and(|&saved| saved > offset) {
+                markers.pop();
+            }
+        }
+    }
+
+    /// Whether a scope or generation was started past `offset`, which keeps
+    /// a freed last block below it from being given back.
+    fn marker_above(&self, offset: usize) -> bool {
+        let above = |markers: &RefCell<Vec<usize>>| {
+            markers.borrow().last().is_some_and(|&saved| saved > offset)
+        };
+        above(&self.scopes) || above(&self.generations)
+    }
+
+    /// Drops records of blocks that were given back, after an offset moved
+    /// down or the top moved up.
+    fn untrack(&self) {
+        #[cfg(feature = "tracking")]
+        self.record.borrow_mut().retain(|&(start, layout)| {
+            start + layout.size() <= self.offset.get() || start >= self.top.get()
+        });
+    }
+
+    /// Updates the record of the block at `start` after it was resized in
+    /// place.
+    #[cfg_attr(not(feature = "tracking"), allow(unused_variables))]
+    fn retrack(&self, start: usize, layout: Layout) {
+        #[cfg(feature = "tracking")]
+        if let Some(record) = self
+            .record
+            .borrow_mut()
+            .iter_mut()
+            .rev()
+            .find(|r| r.0 == start)
+        {
+            record.1 = layout;
+        }
+        self.untrack();
+    }
+
+    /// The bytes of every block still allocated, oldest first within each
+    /// region, e.g. to hunt a leak or dump the layout of objects. Takes
+    /// `&mut self` for the same reason as [`Arena::used_bytes`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`Arena::used_bytes`]: every live block must be fully
+    /// initialized.
+    #[cfg(feature = "tracking")]
+    pub unsafe fn allocations_iter(&mut self) -> impl Iterator<Item = &[u8]> {
+        self.record
+            .get_mut()
+            .iter()
+            .map(|&(start, layout)| unsafe { self.allocation.slice(start..start + layout.size()) })
+    }
+
+    /// How many live blocks were requested at each alignment, e.g. to see
+    /// whether a larger base alignment or granularity would remove most of
+    /// the padding.
+    #[cfg(feature = "tracking")]
+    pub fn alignment_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for &(_, layout) in self.record.borrow().iter() {
+            *histogram.entry(layout.align()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// How many times a collection or smart pointer has freed a block
+    /// through the [`Allocator`] impl, reclaimed or not, e.g. to check that
+    /// dropping a `Vec` frees its buffer. Resets do not clear it, and blocks
+    /// the arena moves itself while growing or shrinking them are not
+    /// counted.
+    #[cfg(feature = "tracking")]
+    pub fn dealloc_count(&self) -> usize {
+        self.dealloc_calls.get()
+    }
+
+    /// Rewinds the arena to empty so its space can be reused.
+    ///
+    /// Allocating only needs `&self`, so this takes `&mut self`: no
+    /// reference from the `alloc*` methods, and no `Box` or `Vec` built on
+    /// `&Arena`, can still be alive to see its space handed out again.
+    ///
+    /// ```compile_fail
+    /// # use arena::Arena;
+    /// let mut arena = Arena::with_capacity(64).unwrap();
+    /// let a = arena.alloc(1u32).unwrap();
+    /// arena.reset();
+    /// *a += 1;
+    /// ```
+    pub fn reset(&mut self) {
+        self.clear_to(0);
+    }
+
+    /// Like [`Arena::reset`], returning how many bytes were in use, e.g. to
+    /// log per-iteration usage in a loop.
+    pub fn reset_returning(&mut self) -> usize {
+        let used = self.used();
+        self.reset();
+        used
+    }
+
+    /// Resets the arena but keeps the last `keep` bytes of the upward region,
+    /// moved to the front, e.g. a parser's lookahead across a sliding window.
+    /// `keep` is clamped to [`Arena::position`]. The kept bytes count as one
+    /// block afterwards.
+    ///
+    /// Like [`Arena::reset`] this takes `&mut self`, since the moved bytes
+    /// end up under the front of the arena.
+    pub fn reset_keeping_tail(&mut self, keep: usize) {
+        let offset = self.offset.get();
+        let keep = keep.min(offset);
+        unsafe { core::ptr::copy(self.base_ptr().add(offset - keep), self.base_ptr(), keep) };
+        self.clear_to(keep);
+        #[cfg(feature = "tracking")]
+        self.record.borrow_mut().clear();
+        if keep != 0 {
+            self.allocations.set(1);
+            self.track(0, Layout::array::<u8>(keep).unwrap());
+        }
+    }
+
+    /// Empties the arena except for the first `keep` bytes of the upward
+    /// region.
+    fn clear_to(&self, keep: usize) {
+        self.poison(keep, self.offset.get());
+        self.poison(self.top.get(), self.allocation.len());
+        self.live.set(0);
+        self.offset.set(keep);
+        // Keeping a tail moves it to the front, so no node survives.
+        self.forget_nodes_from(0);
+        self.wasted.set(0);
+        self.allocations.set(0);
+        self.base.set(0);
+        self.scopes.borrow_mut().clear();
+        self.generations.borrow_mut().clear();
+        self.top.set(self.allocation.len());
+        self.top_align.set(1);
+        self.untrack();
+    }
+
+    /// Like [`Arena::reset`], but refuses while blocks handed out to `Box`,
+    /// `Vec` and other users of the `Allocator` impl have not been freed,
+    /// e.g. because they were leaked, returning how many remain. References
+    /// from the `alloc*` methods are not counted, since nothing frees them.
+    pub fn reset_checked(&mut self) -> Result<(), usize> {
+        match self.live.get() {
+            0 => {
+                self.reset();
+                Ok(())
+            }
+            live => Err(live),
+        }
+    }
+
+    /// Records the current offset as the end of data that should outlive
+    /// [`Arena::reset_to_persistent`], e.g. what a game allocates during
+    /// setup before its frame loop.
+    pub fn mark_persistent(&self) {
+        self.base.set(self.offset.get());
+    }
+
+    /// Frees everything allocated since [`Arena::mark_persistent`], keeping
+    /// what came before. Like [`Arena::reset`] this takes `&mut self`, so
+    /// nothing freed can still be borrowed. Scopes pushed after the mark are
+    /// dropped too.
+    pub fn reset_to_persistent(&mut self) {
+        let base = self.base.get().min(self.offset.get());
+        self.poison(base, self.offset.get());
+        self.offset.set(base);
+        self.forget_nodes_from(base);
+        self.drop_markers_above(base);
+        self.untrack();
+    }
+
+    /// Like [`Arena::reset`], but first overwrites every used byte with zero
+    /// so secrets such as keys do not linger in memory. The writes are
+    /// volatile so they are not optimized away; this is O(used).
+    pub fn reset_zeroing(&mut self) {
+        let (offset, top) = (self.offset.get(), self.top.get());
+        // Reset first so that zeroing is the last write, even when `reset`
+        // poisons the reclaimed bytes.
+        self.reset();
+        let base = self.base_ptr();
+        for i in (0..offset).chain(top..self.allocation.len()) {
+            unsafe { base.add(i).write_volatile(0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Saves the current offset on the arena's scope stack, to be restored by
+    /// the matching [`Arena::pop_scope`].
+    pub fn push_scope(&self) {
+        self.scopes.borrow_mut().push(self.offset.get());
+    }
+
+    /// Restores the offset saved by the innermost [`Arena::push_scope`].
+    ///
+    /// Like [`Arena::reset`] this takes `&mut self`, so nothing allocated
+    /// since the matching push can still be borrowed. Popping more scopes
+    /// than were pushed panics in debug builds and does nothing otherwise.
+    pub fn pop_scope(&mut self) {
+        let saved = self.scopes.borrow_mut().pop();
+        debug_assert!(saved.is_some(), "pop_scope without a matching push_scope");
+        if let Some(saved) = saved {
+            debug_assert!(saved <= self.offset.get(), "scope popped out of order");
+            // Never move forward, even if an entry was missed above.
+            let offset = saved.min(self.offset.get());
+            self.poison(offset, self.offset.get());
+            self.offset.set(offset);
+            self.forget_nodes_from(offset);
+            self.drop_markers_above(offset);
+            self.untrack();
+        }
+    }
+
+    /// Starts a new generation at the current offset and returns its id.
+    /// Ids count up from 0 and are reused once [`Arena::reset_to_generation`]
+    /// has discarded them.
+    pub fn new_generation(&self) -> u32 {
+        let mut generations = self.generations.borrow_mut();
+        generations.push(self.offset.get());
+        (generations.len() - 1) as u32
+    }
+
+    /// Rewinds to where `generation` started, discarding it and every later
+    /// generation while earlier ones keep their allocations.
+    ///
+    /// Like [`Arena::reset`] this takes `&mut self`, so nothing allocated
+    /// since the generation started can still be borrowed. An unknown id
+    /// panics in debug builds and does nothing otherwise.
+    pub fn reset_to_generation(&mut self, generation: u32) {
+        let start = self.generations.borrow().get(generation as usize).copied();
+        debug_assert!(start.is_some(), "no generation {generation}");
+        if let Some(offset) = start {
+            debug_assert!(offset <= self.offset.get(), "generation reset out of order");
+            self.generations.borrow_mut().truncate(generation as usize);
+            self.poison(offset, self.offset.get());
+            self.offset.set(offset);
+            self.forget_nodes_from(offset);
+            self.drop_markers_above(offset);
+            self.untrack();
+        }
+    }
+
+    /// Runs `f` as an all-or-nothing group of allocations: if it returns an
+    /// error, both regions, the counters behind [`Arena::stats`], the scope
+    /// stack and the generations are rolled back to where they were before
+    /// the call, otherwise everything it allocated stays. Only the peak and
+    /// lifetime counters keep what `f` did.
+    ///
+    /// As with [`Arena::with_scope`], `f` must work for any borrow of the
+    /// arena, so no reference into it gets out: not through the result, and
+    /// not stashed elsewhere to dangle after a rollback. Return owned values
+    /// or [`NodeRef`]s instead.
+    ///
+    /// ```compile_fail
+    /// # use arena::{Arena, ArenaError};
+    /// let mut arena = Arena::with_capacity(64).unwrap();
+    /// let mut stash = None;
+    /// let _ = arena.transaction(|arena| {
+    ///     stash = Some(arena.alloc(5u32)?);
+    ///     Err::<(), _>(ArenaError::ZeroCapacity)
+    /// });
+    /// ```
+    pub fn transaction<R, E>(&mut self, f: impl FnOnce(&Self) -> Result<R, E>) -> Result<R, E> {
+        let (offset, top, top_align) = (self.offset.get(), self.top.get(), self.top_align.get());
+        let (wasted, allocations, live) =
+            (self.wasted.get(), self.allocations.get(), self.live.get());
+        let base = self.base.get();
+        let scopes = self.scopes.borrow().len();
+        let generations = self.generations.borrow().len();
+        let result = f(self);
+        if result.is_err() {
+            self.poison(offset, self.offset.get());
+            self.poison(self.top.get(), top);
+            self.offset.set(offset);
+            self.forget_nodes_from(offset);
+            self.top.set(top);
+            self.top_align.set(top_align);
+            self.wasted.set(wasted);
+            self.allocations.set(allocations);
+            self.live.set(live);
+            self.base.set(base);
+            self.scopes.borrow_mut().truncate(scopes);
+            self.generations.borrow_mut().truncate(generations);
+            self.untrack();
+        }
+        result
+    }
+
+    /// Records the current position so it can later be restored with
+    /// [`Arena::rewind`].
+    pub fn checkpoint(&self) -> Marker {
+        Marker(self.offset.get())
+    }
+
+    /// Opens a scope whose allocations are reclaimed when the returned guard
+    /// is dropped. Takes `&mut self` so the arena is only reachable through
+    /// the guard while it lives.
+    pub fn scope(&mut self) -> Scope<'_, A> {
+        Scope {
+            arena: self,
+            marker: self.checkpoint(),
+        }
+    }
+
+    /// Opens a frame, an allocator whose blocks are reclaimed when it is
+    /// dropped. Like [`Arena::scope`] this takes `&mut self`.
+    pub fn frame(&mut self) -> Frame<'_, A> {
+        Frame {
+            arena: self,
+            marker: self.checkpoint(),
+        }
+    }
+
+    /// Runs `f` on a scope of the arena and rewinds once it returns, so `f`
+    /// can allocate temporaries freely. Only owned results get out: `f`
+    /// must work for any borrow of the arena, and `&mut self` keeps it from
+    /// reaching the arena some other way.
+    ///
+    /// ```
+    /// # use arena::Arena;
+    /// let mut arena = Arena::with_capacity(64).unwrap();
+    /// let sum = arena.with_scope(|arena| {
+    ///     let squares = arena.alloc_from_iter((1..=4u32).map(|i| i * i)).unwrap();
+    ///     squares.iter().sum::<u32>()
+    /// });
+    /// assert_eq!((sum, arena.used()), (30, 0));
+    /// ```
+    ///
+    /// A reference into the scope does not compile:
+    ///
+    /// ```compile_fail
+    /// # use arena::Arena;
+    /// let mut arena = Arena::with_capacity(64).unwrap();
+    /// let dangling = arena.with_scope(|arena| arena.alloc(1u32).unwrap());
+    /// ```
+    ///
+    /// Nor does allocating through the outer arena:
+    ///
+    /// ```compile_fail
+    /// # use arena::Arena;
+    /// let mut arena = Arena::with_capacity(64).unwrap();
+    /// let outer = &arena;
+    /// let dangling = arena.with_scope(|_| outer.alloc(1u32).unwrap());
+    /// ```
+    pub fn with_scope<R>(&mut self, f: impl FnOnce(&Arena<'_, A>) -> R) -> R {
+        let scope = self.scope();
+        f(&scope)
+    }
+
+    /// Rolls the arena back to a position saved by [`Arena::checkpoint`].
+    ///
+    /// # Safety
+    ///
+    /// Every allocation made after `marker` was taken becomes invalid and
+    /// must not be used once this returns.
+    pub unsafe fn rewind(&self, marker: Marker) {
+        debug_assert!(
+            marker.0 <= self.offset.get(),
+            "cannot rewind an arena forward"
+        );
+        self.poison(marker.0, self.offset.get());
+        self.offset.set(marker.0);
+        self.forget_nodes_from(marker.0);
+        self.drop_markers_above(marker.0);
+        self.untrack();
+    }
+
+    /// Frees everything allocated in the upward region after `value`, keeping
+    /// `value` itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not in the upward used region of this arena.
+    ///
+    /// # Safety
+    ///
+    /// Every allocation made after `value` becomes invalid and must not be
+    /// used once this returns.
+    pub unsafe fn truncate_after<T>(&self, value: &T) {
+        let ptr = (value as *const T).cast::<u8>();
+        let end = self
+            .offset_of(ptr)
+            .map(|start| start + core::mem::size_of::<T>())
+            .filter(|&end| end <= self.offset.get());
+        let end = end.expect("value is not in the arena's used region");
+        unsafe { self.rewind(Marker(end)) };
+    }
+}
+
+/// Moves `value` into the first of `arenas` with room for it, treating a fixed
+/// set of arenas as one segmented allocator. Returns `None`, dropping
+/// `value`, if none has room.
+pub fn alloc_first_fit<'a, T>(arenas: &[&'a Arena<'_>], value: T) -> Option<&'a mut T> {
+    arenas
+        .iter()
+        .find_map(|arena| arena.alloc_uninit::<T>().ok())
+        .map(|slot| slot.write(value))
+}
+
+/// Resets `arena` if this is its only handle and returns whether it did.
+/// Allocations borrow the `Rc` they came through, so exclusive ownership
+/// proves none are alive, which is what [`Arena::reset`] needs `&mut` for.
+pub fn reset_if_unique(arena: &mut Rc<Arena<'_>>) -> bool {
+    Rc::get_mut(arena).map(|arena| arena.reset()).is_some()
+}
+
+/// Capacity that holds `count` separate blocks of `size` bytes at `align`,
+/// like [`Arena::capacity_for`] but usable in a `const` context, e.g. to size
+/// an [`InlineArena`] or a static buffer. `size` must be a multiple of
+/// `align`, as every type's size is.
+pub const fn required_capacity(size: usize, align: usize, count: usize) -> usize {
+    (align - 1).saturating_add(size.saturating_mul(count))
+}
+
+/// Runs `f` on this thread's scratch arena, which the first call creates with
+/// `capacity` bytes and later calls reuse without reallocating. The arena is
+/// reset once the outermost call returns; a nested call only rewinds what it
+/// allocated, leaving its caller's allocations alone.
+///
+/// # Aborts
+///
+/// If the scratch arena has to be created and cannot be allocated, this
+/// calls [`handle_alloc_error`], which aborts the process by default.
+#[cfg(feature = "std")]
+pub fn with_thread_local_arena<R>(capacity: usize, f: impl FnOnce(&Arena<'_>) -> R) -> R {
+    /// The thread's arena, boxed on first use. Every call derives its
+    /// reference from the same raw pointer, so a nested call's allocations
+    /// do not invalidate the reference the outer call is still using.
+    struct Scratch {
+        arena: Cell<Option<NonNull<Arena<'static>>>>,
+        depth: Cell<usize>,
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            if let Some(arena) = self.arena.get() {
+                drop(unsafe { Box::from_raw(arena.as_ptr()) });
+            }
+        }
+    }
+
+    std::thread_local! {
+        static SCRATCH: Scratch = const {
+            Scratch {
+                arena: Cell::new(None),
+                depth: Cell::new(0),
+            }
+        };
+    }
+
+    /// Undoes one call's allocations, even if `f` panics.
+    struct Guard<'s> {
+        arena: &'s Arena<'static>,
+        depth: &'s Cell<usize>,
+        marker: Marker,
+    }
+
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.depth.set(self.depth.get() - 1);
+            match self.depth.get() {
+                // The outermost `f` has returned, so nothing borrows the
+                // arena any more.
+                0 => self.arena.clear_to(0),
+                // `f` only borrowed the arena, so nothing it allocated is
+                // still alive.
+                _ => unsafe { self.arena.rewind(self.marker) },
+            }
+        }
+    }
+
+    SCRATCH.with(|scratch| {
+        let ptr = scratch.arena.get().unwrap_or_else(|| {
+            let arena = Arena::with_capacity(capacity).unwrap_or_else(|_| {
+                handle_alloc_error(
+                    Layout::array::<u8>(capacity).unwrap_or_else(|_| Layout::new::<u8>()),
+                )
+            });
+            let ptr = NonNull::from(Box::leak(Box::new(arena)));
+            scratch.arena.set(Some(ptr));
+            ptr
+        });
+        let arena = unsafe { ptr.as_ref() };
+        scratch.depth.set(scratch.depth.get() + 1);
+        let guard = Guard {
+            arena,
+            depth: &scratch.depth,
+            marker: arena.checkpoint(),
+        };
+        f(guard.arena)
+    })
+}
+
+/// Padding needed to place `layout` at `offset` into `buffer`, or `None` if
+/// it does not fit in the rest of the buffer.
+fn padding_in(buffer: *const [u8], offset: usize, layout: Layout) -> Option<usize> {
+    padding_for_parts(buffer, offset, layout.size(), layout.align())
+}
+
+/// [`padding_in`] for a raw size and alignment, checking in debug builds the
+/// invariants `Layout` promises, in case a custom call path got them wrong.
+///
+/// The buffer is only looked at for its address and length, so it may be
+/// uninitialized.
+fn padding_for_parts(
+    buffer: *const [u8],
+    offset: usize,
+    size: usize,
+    align: usize,
+) -> Option<usize> {
+    debug_assert!(align.is_power_of_two(), "alignment must be a power of two");
+    debug_assert!(size <= isize::MAX as usize, "size exceeds isize::MAX");
+    // Zero-sized layouts get a dangling pointer and never occupy the buffer.
+    if size == 0 {
+        return Some(0);
+    }
+    let padding = align_padding(buffer.cast::<u8>().addr().wrapping_add(offset), align);
+    let end = offset.checked_add(padding)?.checked_add(size)?;
+    if end > buffer.len() {
+        return None;
+    }
+    Some(padding)
+}
+
+/// The page size to step by when touching memory. Without `mmap` there is no
+/// way to ask the OS, so this assumes the common 4 KiB.
+fn page_size() -> usize {
+    #[cfg(all(feature = "mmap", unix))]
+    return mapped::page_size();
+    #[cfg(not(all(feature = "mmap", unix)))]
+    4096
+}
+
+/// Bytes from `addr` up to the next multiple of `align`, a power of two. This
+/// is on the path of every allocation, so it masks instead of dividing.
+#[inline]
+fn align_padding(addr: usize, align: usize) -> usize {
+    addr.wrapping_neg() & (align - 1)
+}
+
+/// Forks the arena into a new buffer with the same capacity, contents and
+/// offset.
+///
+/// Pointers stored inside the arena are copied as-is, so any self-referential
+/// data in the clone still points into the original.
+impl<A: Allocator + Clone> Clone for Arena<'_, A> {
+    fn clone(&self) -> Self {
+        let align = self.allocation.align();
+        let (allocation, top) = self.relocated(self.capacity(), align).unwrap_or_else(|_| {
+            handle_alloc_error(Layout::from_size_align(self.capacity(), align).unwrap())
+        });
+        let mut clone = Self::from_storage_in(allocation, self.alloc.clone());
+        clone.granularity = self.granularity;
+        clone.offset.set(self.offset.get());
+        clone.top.set(top);
+        clone.top_align.set(self.top_align.get());
+        clone.peak.set(self.peak.get());
+        clone.cycle_peak.set(self.cycle_peak.get());
+        clone.recent_peaks = self.recent_peaks;
+        clone.cycles = self.cycles;
+        clone.zeroed.set(self.zeroed.get());
+        clone.wasted.set(self.wasted.get());
+        clone.allocations.set(self.allocations.get());
+        clone.lifetime_bytes.set(self.lifetime_bytes.get());
+        clone.base.set(self.base.get());
+        clone.epoch.set(self.epoch.get());
+        clone.nodes_end.set(self.nodes_end.get());
+        #[cfg(feature = "tracking")]
+        clone.dealloc_calls.set(self.dealloc_calls.get());
+        clone.scopes.borrow_mut().clone_from(&self.scopes.borrow());
+        clone
+            .generations
+            .borrow_mut()
+            .clone_from(&self.generations.borrow());
+        #[cfg(feature = "tracking")]
+        clone
+            .record
+            .borrow_mut()
+            .extend(self.record.borrow().iter().map(|&(start, layout)| {
+                match start >= self.top.get() {
+                    true => (start - self.top.get() + top, layout),
+                    false => (start, layout),
+                }
+            }));
+        clone
+    }
+}
+
+impl<A: Allocator> fmt::Display for Arena<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = match self.capacity() {
+            0 => 0,
+            capacity => self.used() as u128 * 100 / capacity as u128,
+        };
+        write!(
+            f,
+            "Arena {{ used: {}/{} bytes ({}%), peak: {} }}",
+            self.used(),
+            self.capacity(),
+            percent,
+            self.peak_used()
+        )
+    }
+}
+
+impl Default for Arena<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Arena<'a> {
+    /// Creates an arena that bumps into `buf` instead of allocating its own
+    /// backing memory, e.g. a stack array or a `static` buffer.
+    pub fn from_buffer(buf: &'a mut [u8]) -> Self {
+        Self::from_storage(Storage::borrowed(buf))
+    }
+
+    /// Starts formatting a string into the arena; see [`ArenaWriter`].
+    pub fn writer(&'a self) -> ArenaWriter<'a> {
+        ArenaWriter::new(self)
+    }
+
+    fn from_storage(allocation: Storage<'a>) -> Self {
+        Self::from_storage_in(allocation, Global)
+    }
+
+    /// Cuts the buffer in two at `at`: this arena keeps `[0, at)` and the
+    /// returned one gets the rest, each with its own offset, without a
+    /// second allocation. An owned buffer is freed once both halves are
+    /// dropped. Taking `&mut self` guarantees nothing allocated is still
+    /// borrowed, so both halves start empty. Fails if `at` is past the
+    /// capacity.
+    pub fn split_off(&mut self, at: usize) -> Result<Arena<'a>, AllocError> {
+        if at > self.capacity() {
+            return Err(AllocError);
+        }
+        let storage = core::mem::replace(&mut self.allocation, Storage::borrowed(&mut []));
+        let (first, second) = storage.split(at);
+        let granularity = self.granularity;
+        *self = Self::from_storage(first);
+        self.granularity = granularity;
+        let mut rest = Self::from_storage(second);
+        rest.granularity = granularity;
+        Ok(rest)
+    }
+}
+
+impl<'a, A: Allocator> Arena<'a, A> {
+    fn from_storage_in(allocation: Storage<'a>, alloc: A) -> Self {
+        Self {
+            offset: Cell::new(0),
+            top: Cell::new(allocation.len()),
+            top_align: Cell::new(1),
+            peak: Cell::new(0),
+            cycle_peak: Cell::new(0),
+            recent_peaks: [0; RECENT_PEAKS],
+            cycles: 0,
+            zeroed: Cell::new((0, 0)),
+            wasted: Cell::new(0),
+            allocations: Cell::new(0),
+            lifetime_bytes: Cell::new(0),
+            granularity: 1,
+            live: Cell::new(0),
+            base: Cell::new(0),
+            epoch: Cell::new(0),
+            nodes_end: Cell::new(0),
+            #[cfg(feature = "tracking")]
+            record: RefCell::new(Vec::new()),
+            #[cfg(feature = "tracking")]
+            dealloc_calls: Cell::new(0),
+            scopes: RefCell::new(Vec::new()),
+            generations: RefCell::new(Vec::new()),
+            allocation,
+            alloc,
+        }
+    }
+
+    /// Swaps in a new backing buffer, freeing the old one.
+    fn replace_storage(&mut self, allocation: Storage<'a>) {
+        let old = core::mem::replace(&mut self.allocation, allocation);
+        self.free_storage(old);
+    }
+
+    fn free_storage(&self, storage: Storage<'a>) {
+        if let Storage::Owned { ptr, layout } = storage {
+            if layout.size() != 0 {
+                unsafe { self.alloc.deallocate(ptr, layout) };
+            }
+        }
+    }
+}
+
+impl<A: Allocator> Drop for Arena<'_, A> {
+    fn drop(&mut self) {
+        let storage = core::mem::replace(&mut self.allocation, Storage::borrowed(&mut []));
+        self.free_storage(storage);
+    }
+}
+
+/// Lets std collections and smart pointers, including `Rc::new_in` and
+/// `Arc::new_in`, allocate from the arena. Freeing only reclaims space when
+/// the block is the most recent one; the rest is reclaimed by a reset.
+unsafe impl<A: Allocator> Allocator for &Arena<'_, A> {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate_raw(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let (zeroed_start, zeroed_end) = self.zeroed.get();
+        let ptr = self.allocate(layout)?;
+        let start = (ptr.cast::<u8>().as_ptr() as usize).wrapping_sub(self.base_ptr() as usize);
+        // Untouched bytes of a zeroed buffer are still zero; anything else may
+        // hold stale bytes from before a `reset`.
+        if layout.size() != 0 && !(start >= zeroed_start && start + layout.size() <= zeroed_end) {
+            unsafe { core::ptr::write_bytes(ptr.cast::<u8>().as_ptr(), 0, layout.size()) };
+        }
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "tracking")]
+        self.dealloc_calls.set(self.dealloc_calls.get() + 1);
+        unsafe { self.free_block(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // The last block can be extended in place by moving the bump position.
+        if ptr.as_ptr().align_offset(new_layout.align()) == 0
+            && self
+                .grow_last(ptr.as_ptr(), old_layout.size(), new_layout)
+                .is_ok()
+        {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+        // Relocate to the current offset, the start of the free space, so the
+        // new block gets the least padding possible. The old block is now
+        // followed by it and stays behind as dead space.
+        let new_ptr = self.allocate(new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.cast::<u8>().as_ptr(),
+                old_layout.size(),
+            );
+            self.free_block(ptr, old_layout);
+        }
+        if self.owns(ptr.as_ptr()) {
+            self.wasted.set(self.wasted.get() + old_layout.size());
+        }
+        Ok(new_ptr)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = unsafe { self.grow(ptr, old_layout, new_layout)? };
+        unsafe {
+            core::ptr::write_bytes(
+                new_ptr.cast::<u8>().as_ptr().add(old_layout.size()),
+                0,
+                new_layout.size() - old_layout.size(),
+            )
+        };
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // The data already sits in place, so only copy if the alignment got
+        // stricter. The tail is given back if this is the last block, the
+        // same check `grow` and `deallocate` use; otherwise it is dead space.
+        if ptr.as_ptr().align_offset(new_layout.align()) == 0 {
+            let start = self.offset.get().wrapping_sub(old_layout.size());
+            if self.is_last_block(ptr.as_ptr(), old_layout.size())
+                && !self.marker_above(start + new_layout.size())
+            {
+                self.offset.set(start + new_layout.size());
+                self.retrack(start, new_layout);
+            } else if self.owns(ptr.as_ptr()) {
+                self.wasted
+                    .set(self.wasted.get() + old_layout.size() - new_layout.size());
+            }
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+        let new_ptr = self.allocate(new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.cast::<u8>().as_ptr(),
+                new_layout.size(),
+            );
+            self.free_block(ptr, old_layout);
+        }
+        // As in `grow`, the old block is followed by the new one.
+        if self.owns(ptr.as_ptr()) {
+            self.wasted.set(self.wasted.get() + old_layout.size());
+        }
+        Ok(new_ptr)
+    }
+}
+
+/// Same as the `&Arena` impl, for APIs that want a pinned allocator handle.
+/// The buffer never moves while the arena is borrowed, pinned or not.
+unsafe impl<A: Allocator> Allocator for core::pin::Pin<&Arena<'_, A>> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&**self).allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&**self).allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (&**self).deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { (&**self).grow(ptr, old_layout, new_layout) }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { (&**self).shrink(ptr, old_layout, new_layout) }
+    }
+}
+
+/// Forwards to the frame's arena; see [`Frame`].
+unsafe impl<A: Allocator> Allocator for &Frame<'_, A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.arena.allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { self.arena.deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { self.arena.grow(ptr, old_layout, new_layout) }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { self.arena.shrink(ptr, old_layout, new_layout) }
+    }
+}
+
+// These tests are meant to pass under `cargo +nightly miri test` as well, so
+// any test that depends on alignment asks for an aligned buffer instead of
+// relying on the global allocator to over-align.
+#[cfg(test)]
+mod tests {
+    use crate::{
+        align_padding, alloc_first_fit, padding_for_parts, reset_if_unique, Arena, ArenaError,
+        ChunkedArena, FitResult, NodeRef, Stats, SyncArena, TypedArena,
+    };
+    use std::alloc::{Allocator, Layout};
+
+    #[test]
+    fn it_works() {
+        let arena = Arena::with_capacity(1024).unwrap();
+        let a = Box::new_in(5.0, &arena);
+        assert_eq!(a.as_ref(), &5.0);
+        let b = Box::new_in(12, &arena);
+        assert_eq!(b.as_ref(), &12)
+    }
+
+    #[test]
+    fn it_works_vec() {
+        let arena = Arena::with_capacity(24).unwrap();
+        let mut a: Vec<u8, _> = Vec::new_in(&arena);
+        a.extend(0..10);
+        assert_eq!(&a, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_allignement() {
+        let arena = Arena::with_capacity(32).unwrap();
+        let a = Box::new_in(5u8, &arena);
+        assert_eq!(a.as_ref(), &5);
+        let b = Box::new_in(u128::MAX, &arena);
+        assert_eq!(b.as_ref(), &u128::MAX);
+        assert_eq!(
+            (b.as_ref() as *const u128) as usize % std::mem::align_of_val(b.as_ref()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_pinned_handle() {
+        let arena = Arena::with_capacity(256).unwrap();
+        let pinned = core::pin::Pin::new(&arena);
+        let boxed = Box::new_in(41u32, pinned);
+        let mut v = Vec::new_in(pinned);
+        v.extend(0..10u8);
+        assert_eq!(*boxed + 1, 42);
+        assert_eq!(v.iter().map(|&x| x as u32).sum::<u32>(), 45);
+        assert!(arena.owns(v.as_ptr()));
+    }
+
+    #[test]
+    fn test_allocate_raw() {
+        let mut arena = Arena::with_capacity(128).unwrap();
+        arena.alloc(1u8).unwrap();
+        let layout = Layout::from_size_align(48, 16).unwrap();
+        let block = arena.allocate_raw(layout).unwrap();
+        assert_eq!(block.len(), 48);
+        assert_eq!(block.cast::<u8>().addr().get() % 16, 0);
+        assert!(arena.reset_checked().is_err());
+        unsafe { (&arena).deallocate(block.cast(), layout) };
+        assert!(arena.reset_checked().is_ok());
+    }
+
+    #[test]
+    fn test_spread_of_alignments() {
+        // `allocate` debug-asserts alignment itself; the explicit checks keep
+        // this meaningful in release builds.
+        let arena = Arena::with_capacity(1024).unwrap();
+        for align in [2, 4, 8, 16, 32, 64] {
+            for size in [1, 3, align] {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                arena.alloc(1u8).unwrap();
+                let up = (&arena).allocate(layout).unwrap();
+                let down = arena.allocate_down(layout).unwrap();
+                assert_eq!(up.cast::<u8>().addr().get() % align, 0);
+                assert_eq!(down.cast::<u8>().addr().get() % align, 0);
+            }
+        }
+    }
+    #[test]
+    #[should_panic(expected = "arena out of space: needed 48, have 12")]
+    fn test_alloc_or_panic_message() {
+        let arena = Arena::with_capacity_aligned(16, 8).unwrap();
+        *arena.alloc_or_panic(1u32) += 1;
+        arena.alloc_or_panic([0u8; 48]);
+    }
+
+    #[test]
+    fn test_fit() {
+        let arena = Arena::with_capacity(24).unwrap();
+        assert!(arena.can_fit::<u8>());
+        assert!(!arena.can_fit_slice::<u8>(200));
+        assert!(arena.can_fit_slice::<u8>(24));
+        let mut a: Vec<u8, _> = Vec::new_in(&arena);
+        a.extend(0..24);
+        assert!(!arena.can_fit::<u8>());
+    }
+
+    #[test]
+    fn test_plan() {
+        let arena = Arena::with_capacity_aligned(40, 16).unwrap();
+        let layouts = [
+            Layout::new::<u8>(),
+            Layout::new::<u128>(),
+            Layout::new::<u8>(),
+            Layout::new::<u128>(),
+        ];
+        assert_eq!(arena.plan(layouts), 3);
+        assert_eq!(arena.used(), 0);
+        let fitted = layouts
+            .iter()
+            .take_while(|&&layout| (&arena).allocate(layout).is_ok())
+            .count();
+        assert_eq!(fitted, 3);
+        assert_eq!(arena.plan([Layout::new::<()>(), Layout::new::<u8>()]), 2);
+        assert_eq!(arena.plan([]), 0);
+    }
+
+    #[test]
+    fn test_can_fit_all() {
+        let arena = Arena::with_capacity_aligned(24, 8).unwrap();
+        let mut layouts = vec![
+            Layout::new::<u8>(),
+            Layout::new::<u64>(),
+            Layout::new::<u64>(),
+        ];
+        assert!(arena.can_fit_all(&layouts));
+        assert!(arena.can_fit_all(&[]));
+        layouts.push(Layout::new::<u8>());
+        assert!(!arena.can_fit_all(&layouts));
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_classify_fit() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        assert_eq!(
+            arena.classify_fit(Layout::new::<[u8; 63]>()),
+            FitResult::Fits
+        );
+        let big = Layout::new::<[u8; 163]>();
+        assert_eq!(arena.classify_fit(big), FitResult::NeedsGrowth(100));
+        arena.grow_to(164).unwrap();
+        assert_eq!(arena.classify_fit(big), FitResult::Fits);
+        let huge = Layout::from_size_align(isize::MAX as usize - 7, 8).unwrap();
+        assert_eq!(arena.classify_fit(huge), FitResult::ImpossibleOverflow);
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let arena = Arena::with_capacity(8).unwrap();
+        let _ = Box::new_in((), &arena);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut arena = Arena::with_capacity(16).unwrap();
+        let mut a: Vec<u8, _> = Vec::with_capacity_in(16, &arena);
+        a.extend(0..16);
+        assert!(!arena.can_fit::<u8>());
+        drop(a);
+        arena.reset();
+        assert!(arena.can_fit_slice::<u8>(16));
+        let mut b: Vec<u8, _> = Vec::with_capacity_in(16, &arena);
+        b.extend(16..32);
+        assert_eq!(b.first(), Some(&16));
+    }
+
+    #[test]
+    fn test_reset_returning() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc([0u8; 40]).unwrap();
+        assert_eq!(arena.reset_returning(), 40);
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.reset_returning(), 0);
+    }
+
+    #[test]
+    fn test_used_remaining() {
+        let arena = Arena::with_capacity(24).unwrap();
+        assert_eq!(arena.capacity(), 24);
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.remaining(), 24);
+        let _a = Box::new_in([0u8; 10], &arena);
+        assert_eq!(arena.used(), 10);
+        assert_eq!(arena.remaining(), 14);
+        assert_eq!(arena.used() + arena.remaining(), arena.capacity());
+    }
+
+    #[test]
+    fn test_set_offset() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        let saved = arena.offset();
+        assert_eq!(saved, arena.position());
+        arena.alloc(2u64).unwrap();
+        arena.alloc([3u8; 5]).unwrap();
+        unsafe { arena.set_offset(saved) };
+        assert_eq!(arena.offset(), saved);
+        assert_eq!(arena.used(), 1);
+        unsafe { arena.set_offset(32) };
+        assert_eq!(arena.offset(), 32);
+        assert_eq!(arena.peak_used(), 32);
+        assert_eq!(arena.remaining(), 32);
+        assert!(arena.owns(arena.alloc(4u8).unwrap()));
+        assert_eq!(arena.position(), 33);
+    }
+
+    #[test]
+    fn test_checkpoint_rewind() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let a = Box::new_in(1u32, &arena);
+        let marker = arena.checkpoint();
+        let used = arena.used();
+        for _ in 0..4 {
+            let scratch = Box::new_in([7u8; 32], &arena);
+            assert_eq!(scratch[31], 7);
+            drop(scratch);
+            unsafe { arena.rewind(marker) };
+            assert_eq!(arena.used(), used);
+        }
+        assert_eq!(*a, 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "cannot rewind an arena forward")]
+    fn test_rewind_forward() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u32).unwrap();
+        let marker = arena.checkpoint();
+        arena.reset();
+        unsafe { arena.rewind(marker) };
+    }
+
+    #[test]
+    fn test_reset_keeping_tail() {
+        let mut arena = Arena::with_capacity(128).unwrap();
+        arena.alloc_from_iter(0..100u8).unwrap();
+        arena.alloc_down(7u64).unwrap();
+        arena.reset_keeping_tail(10);
+        assert_eq!(arena.used(), 10);
+        assert_eq!(arena.position(), 10);
+        assert_eq!(arena.remaining(), 118);
+        assert!(unsafe { arena.slice_at(0, 10) }.iter().copied().eq(90..100));
+        arena.reset_keeping_tail(1000);
+        assert_eq!(arena.used(), 10);
+        arena.reset_keeping_tail(0);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_after() {
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let a = arena.alloc(1u32).unwrap();
+        arena.alloc(2u64).unwrap();
+        arena.alloc([3u8; 16]).unwrap();
+        unsafe { arena.truncate_after(a) };
+        assert_eq!(arena.used(), 4);
+        assert_eq!(*a, 1);
+        assert_eq!(arena.remaining(), 60);
+    }
+
+    #[test]
+    fn test_pop() {
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let a: *const u32 = arena.alloc(1u32).unwrap();
+        let b: *const u64 = arena.alloc(2u64).unwrap();
+        let (a_layout, b_layout) = (Layout::new::<u32>(), Layout::new::<u64>());
+        assert!(arena.is_last_allocation(b.cast(), b_layout));
+        assert!(!arena.is_last_allocation(a.cast(), a_layout));
+
+        unsafe { arena.pop(a.cast(), a_layout) };
+        assert_eq!(arena.used(), 16);
+        unsafe { arena.pop(b.cast(), b_layout) };
+        assert_eq!(arena.used(), 8);
+        assert!(!arena.is_last_allocation(a.cast(), a_layout));
+    }
+
+    #[test]
+    #[should_panic(expected = "not in the arena's used region")]
+    fn test_truncate_after_foreign_value() {
+        let arena = Arena::with_capacity(64).unwrap();
+        unsafe { arena.truncate_after(&0u32) };
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let arena = Arena::with_capacity(0).unwrap();
+        assert_eq!(arena.capacity(), 0);
+        assert!(!arena.can_fit::<u8>());
+        assert!(Box::try_new_in(1u8, &arena).is_err());
+    }
+
+    #[test]
+    // Miri aborts on an allocation it cannot serve instead of failing it.
+    #[cfg_attr(miri, ignore)]
+    fn test_capacity_too_large() {
+        assert!(Arena::with_capacity(isize::MAX as usize).is_err());
+        assert!(Arena::with_capacity(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_exact_fit_with_padding() {
+        let arena = Arena::with_capacity(32).unwrap();
+        let _a = Box::new_in(1u8, &arena);
+        let align = 8;
+        let padding = arena
+            .allocation
+            .as_ptr()
+            .wrapping_add(arena.used())
+            .align_offset(align);
+        let layout = Layout::from_size_align(arena.remaining() - padding, align).unwrap();
+        assert!((&arena).allocate(layout).is_ok());
+        assert_eq!(arena.remaining(), 0);
+    }
+
+    #[test]
+    fn test_allocate_zeroed() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        let dirty = Box::new_in([0xFFu8; 64], &arena);
+        drop(dirty);
+        arena.reset();
+        let zeroed = Box::<[u8; 64], _>::new_zeroed_in(&arena);
+        let zeroed = unsafe { zeroed.assume_init() };
+        assert!(zeroed.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_grow_to() {
+        let mut arena = Arena::with_capacity(16).unwrap();
+        let mut a: Vec<u8, _> = Vec::with_capacity_in(16, &arena);
+        a.extend(0..16);
+        std::mem::forget(a);
+        assert!(!arena.can_fit::<u8>());
+        arena.grow_to(1024).unwrap();
+        assert_eq!(arena.capacity(), 1024);
+        assert_eq!(arena.used(), 16);
+        assert_eq!(
+            unsafe { arena.used_bytes() },
+            &(0..16).collect::<Vec<u8>>()[..]
+        );
+        assert!(arena.can_fit_slice::<u8>(1008));
+    }
+
+    #[test]
+    fn test_alloc() {
+        #[derive(Debug, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+        let arena = Arena::with_capacity(64).unwrap();
+        let p = arena.alloc(Point { x: 1, y: 2 }).unwrap();
+        p.x += 10;
+        let q = arena.alloc(Point { x: 3, y: 4 }).unwrap();
+        assert_eq!(p, &Point { x: 11, y: 2 });
+        assert_eq!(q, &Point { x: 3, y: 4 });
+        assert!(arena.alloc([0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_alloc_slice_copy() {
+        let arena = Arena::with_capacity(128).unwrap();
+        let bytes = arena.alloc_slice_copy(b"hello").unwrap();
+        assert_eq!(bytes, b"hello");
+        let wide = arena.alloc_slice_copy(&[1u128, 2, 3]).unwrap();
+        assert_eq!(wide, &[1, 2, 3]);
+        assert_eq!(wide.as_ptr() as usize % std::mem::align_of::<u128>(), 0);
+        let used = arena.used();
+        let empty = arena.alloc_slice_copy::<u64>(&[]).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(arena.used(), used);
+    }
+
+    #[test]
+    fn test_alloc_str() {
+        let arena = Arena::with_capacity(32).unwrap();
+        let a = arena.alloc_str("foo").unwrap();
+        let b = arena.alloc_str("barbaz").unwrap();
+        assert_eq!(a, "foo");
+        assert_eq!(b, "barbaz");
+        let a_end = a.as_ptr() as usize + a.len();
+        assert!(a_end <= b.as_ptr() as usize);
+        assert_eq!(arena.used(), 9);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut arena = Arena::with_capacity_aligned(1024, 8).unwrap();
+        arena.alloc(1u64).unwrap();
+        let second = arena.split_off(512).unwrap();
+        assert_eq!((arena.capacity(), arena.used()), (512, 0));
+        assert_eq!(second.capacity(), 512);
+
+        let a = arena.alloc_slice_copy(&[1u64; 64]).unwrap();
+        let b = second.alloc_slice_copy(&[2u64; 64]).unwrap();
+        assert!(arena.owns(a.as_ptr().cast()) && !second.owns(a.as_ptr().cast()));
+        assert!(second.owns(b.as_ptr().cast()));
+        assert!(arena.alloc(0u8).is_err() && second.alloc(0u8).is_err());
+        assert_eq!((a[63], b[63]), (1, 2));
+
+        // The buffer outlives whichever half is dropped first.
+        drop(arena);
+        let mut second = second;
+        let third = second.split_off(256).unwrap();
+        drop(second);
+        assert_eq!(*third.alloc(3u32).unwrap(), 3);
+
+        let mut buf = [0u8; 64];
+        let mut borrowed = Arena::from_buffer(&mut buf);
+        assert!(borrowed.split_off(65).is_err());
+        let rest = borrowed.split_off(64).unwrap();
+        assert_eq!((borrowed.capacity(), rest.capacity()), (64, 0));
+    }
+
+    #[test]
+    fn test_from_buffer() {
+        let mut buf = [0u8; 64];
+        let range = buf.as_ptr_range();
+        let arena = Arena::from_buffer(&mut buf);
+        assert_eq!(arena.capacity(), 64);
+        let a = arena.alloc(42u64).unwrap();
+        let b = arena.alloc_str("stack").unwrap();
+        assert_eq!(*a, 42);
+        assert_eq!(b, "stack");
+        assert!(range.contains(&(a as *const u64 as *const u8)));
+        assert!(range.contains(&b.as_ptr()));
+        assert!(arena.alloc([0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_deallocate_last_block() {
+        let arena = Arena::with_capacity(1024).unwrap();
+        let keep = Box::new_in(1u8, &arena);
+        let used = arena.used();
+        for i in 0..1000u64 {
+            let mut v: Vec<u64, _> = Vec::with_capacity_in(64, &arena);
+            v.extend(i..i + 64);
+            assert_eq!(v[63], i + 63);
+        }
+        // Each vector's buffer was the last block when it was dropped, so
+        // only the alignment padding of the first one stays used.
+        assert!(arena.used() < used + 8);
+
+        let a = Box::new_in(2u32, &arena);
+        let b = Box::new_in(3u32, &arena);
+        let used = arena.used();
+        drop(a);
+        assert_eq!(arena.used(), used);
+        drop(b);
+        assert_eq!(arena.used(), used - 4);
+        assert_eq!(*keep, 1);
+    }
+
+    #[test]
+    fn test_grow_in_place() {
+        let arena = Arena::with_capacity(32).unwrap();
+        let mut v: Vec<u8, _> = Vec::with_capacity_in(16, &arena);
+        v.extend(0..16);
+        let ptr = v.as_ptr();
+        // Copying into a fresh 32-byte block could not fit next to the
+        // existing 16 bytes, so this only succeeds in place.
+        v.extend(16..32);
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(v, (0..32).collect::<Vec<u8>>());
+        assert_eq!(arena.used(), 32);
+    }
+
+    #[test]
+    fn test_grow_copies_when_not_last() {
+        let arena = Arena::with_capacity(128).unwrap();
+        let mut v: Vec<u8, _> = Vec::with_capacity_in(8, &arena);
+        v.extend(0..8);
+        let _other = Box::new_in(1u8, &arena);
+        let ptr = v.as_ptr();
+        v.extend(8..16);
+        assert_ne!(v.as_ptr(), ptr);
+        assert_eq!(v, (0..16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_grow_zeroed() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let old = Layout::array::<u8>(8).unwrap();
+        let new = Layout::array::<u8>(32).unwrap();
+        drop(Box::new_in([0xAAu8; 64], &arena));
+        let ptr = (&arena).allocate(old).unwrap().cast::<u8>();
+        unsafe {
+            ptr.as_ptr().write_bytes(1, 8);
+            let grown = (&arena).grow_zeroed(ptr, old, new).unwrap();
+            let bytes = grown.as_ref();
+            assert_eq!(&bytes[..8], &[1; 8]);
+            assert!(bytes[8..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn test_default() {
+        #[derive(Default)]
+        struct Holder<'a> {
+            arena: Arena<'a>,
+        }
+        let mut holder = Holder::default();
+        assert_eq!(holder.arena.remaining(), 0);
+        assert_eq!(Arena::new().capacity(), 0);
+        holder.arena.grow_to(16).unwrap();
+        assert_eq!(*holder.arena.alloc(3u8).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_with_capacity_aligned() {
+        #[repr(align(64))]
+        struct CacheLine([u8; 64]);
+
+        let arena = Arena::with_capacity_aligned(64, 64).unwrap();
+        let line = arena.alloc(CacheLine([1; 64])).unwrap();
+        assert_eq!(line.0[63], 1);
+        assert_eq!(line as *const CacheLine as usize % 64, 0);
+        assert_eq!(arena.used(), 64);
+        assert!(Arena::with_capacity_aligned(64, 3).is_err());
+        assert_eq!(Arena::with_capacity_aligned(0, 64).unwrap().capacity(), 0);
+    }
+
+    #[test]
+    fn test_grow_to_keeps_alignment() {
+        let mut arena = Arena::with_capacity_aligned(64, 64).unwrap();
+        arena.alloc(7u8).unwrap();
+        arena.grow_to(256).unwrap();
+        assert_eq!(arena.allocation.as_ptr() as usize % 64, 0);
+        assert_eq!(unsafe { arena.used_bytes() }[0], 7);
+    }
+
+    #[test]
+    fn test_owns() {
+        let arena = Arena::with_capacity(16).unwrap();
+        let a = arena.alloc(1u32).unwrap();
+        let heap = Box::new(1u32);
+        assert!(arena.owns(a as *const u32 as *const u8));
+        assert!(!arena.owns(&*heap as *const u32 as *const u8));
+        assert!(!arena.owns(arena.allocation.as_ptr().wrapping_add(16)));
+    }
+
+    #[test]
+    fn test_as_ptr_range() {
+        let arena = Arena::with_capacity(16).unwrap();
+        let range = arena.as_ptr_range();
+        assert_eq!(range.end as usize - range.start as usize, 16);
+        let a: *const u32 = arena.alloc(1u32).unwrap();
+        assert!(range.contains(&a.cast()));
+        let heap = Box::new(1u32);
+        assert!(!range.contains(&(&*heap as *const u32).cast()));
+        assert!(Arena::new().as_ptr_range().is_empty());
+    }
+
+    #[test]
+    fn test_remaining_count() {
+        let arena = Arena::with_capacity_aligned(24, 16).unwrap();
+        assert_eq!(arena.remaining_count::<u8>(), 24);
+        assert_eq!(arena.remaining_count::<u128>(), 1);
+        assert_eq!(arena.remaining_count::<()>(), usize::MAX);
+        let n = arena.remaining_count::<u16>();
+        assert!(arena.can_fit_slice::<u16>(n));
+        assert!(!arena.can_fit_slice::<u16>(n + 1));
+
+        // Blocks start on the granularity even for bytes.
+        let arena = Arena::builder()
+            .capacity(64)
+            .base_align(16)
+            .granularity(16)
+            .build()
+            .unwrap();
+        arena.alloc(1u8).unwrap();
+        assert_eq!(arena.remaining_count::<u8>(), 48);
+        assert!(arena.can_fit_slice::<u8>(48));
+        assert!(!arena.can_fit_slice::<u8>(49));
+        assert_eq!(arena.alloc_slice_copy_saturating(&[7u8; 63]).len(), 48);
+    }
+
+    #[test]
+    fn test_zero_sized_types() {
+        #[repr(align(16))]
+        struct Aligned;
+
+        let arena = Arena::with_capacity(8).unwrap();
+        let _fill = Box::new_in([0u8; 8], &arena);
+        assert!(arena.can_fit::<()>());
+        assert!(arena.can_fit_slice::<Aligned>(1000));
+        let unit = Box::new_in((), &arena);
+        let aligned = Box::new_in(Aligned, &arena);
+        assert_eq!(&*aligned as *const Aligned as usize % 16, 0);
+        let mut v: Vec<(), _> = Vec::new_in(&arena);
+        for _ in 0..10_000 {
+            v.push(());
+        }
+        assert_eq!(v.len(), 10_000);
+        assert_eq!(arena.used(), 8);
+        drop(unit);
+        assert_eq!(arena.used(), 8);
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        let a: *const u32 = arena.alloc(1u32).unwrap();
+        let used = arena.used();
+        {
+            let scope = arena.scope();
+            let tmp = scope.alloc_slice_copy(&[9u8; 48]).unwrap();
+            assert_eq!(tmp[47], 9);
+            assert!(scope.used() > 48);
+        }
+        assert_eq!(arena.used(), used);
+        assert_eq!(unsafe { *a }, 1);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut arena = Arena::with_capacity(128).unwrap();
+        let a: *const u32 = arena.alloc(1u32).unwrap();
+        let used = arena.used();
+        {
+            let frame = arena.frame();
+            let mut v = Vec::new_in(&frame);
+            v.extend(0..16u32);
+            assert_eq!(v.iter().sum::<u32>(), 120);
+            assert!(frame.arena.used() >= used + 64);
+        }
+        assert_eq!(arena.used(), used);
+        assert_eq!(unsafe { *a }, 1);
+    }
+
+    #[test]
+    fn test_reset_adaptive() {
+        let mut arena = Arena::with_capacity(1024).unwrap();
+        let cycle = |arena: &mut Arena, used: usize| {
+            arena.alloc_bytes(used).unwrap();
+            arena.reset_adaptive().suggested_capacity
+        };
+        assert_eq!(cycle(&mut arena, 600), 1024);
+        assert_eq!(cycle(&mut arena, 900), 2048);
+        assert_eq!(arena.used(), 0);
+
+        // One busy cycle keeps the suggestion up until it leaves the window.
+        for _ in 0..crate::RECENT_PEAKS - 1 {
+            assert_eq!(cycle(&mut arena, 100), 2048);
+        }
+        assert_eq!(cycle(&mut arena, 100), 200);
+
+        // Allocations rewound within a cycle still count towards its peak.
+        let mark = arena.checkpoint();
+        arena.alloc_bytes(1000).unwrap();
+        unsafe { arena.rewind(mark) };
+        assert_eq!(cycle(&mut arena, 0), 2048);
+    }
+
+    #[test]
+    fn test_lifetime_allocated() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.alloc(2u64).unwrap();
+        assert_eq!(arena.lifetime_allocated(), 16);
+        arena.reset();
+        assert_eq!(arena.lifetime_allocated(), 16);
+        arena.alloc_down(3u32).unwrap();
+        arena.alloc_str("hello").unwrap();
+        assert_eq!(arena.lifetime_allocated(), 25);
+    }
+
+    #[test]
+    fn test_peak_used() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc([0u8; 40]).unwrap();
+        assert_eq!(arena.peak_used(), 40);
+        arena.reset();
+        arena.alloc([0u8; 10]).unwrap();
+        assert_eq!(arena.used(), 10);
+        assert_eq!(arena.peak_used(), 40);
+    }
+
+    #[test]
+    fn test_alloc_default() {
+        #[derive(Debug, Default, PartialEq)]
+        struct Config {
+            retries: u32,
+            name: Option<&'static str>,
+            verbose: bool,
+        }
+        let arena = Arena::with_capacity(64).unwrap();
+        let config = arena.alloc_default::<Config>().unwrap();
+        assert_eq!(config, &Config::default());
+        config.retries = 3;
+        assert_eq!(config.retries, 3);
+    }
+
+    #[test]
+    fn test_alloc_with() {
+        let arena = Arena::with_capacity(8192).unwrap();
+        let page = arena.alloc_with(|| [7u64; 512]).unwrap();
+        assert_eq!(std::mem::size_of_val(page), 4096);
+        assert!(page.iter().all(|&x| x == 7));
+        assert!(arena.alloc_with(|| [0u64; 1024]).is_err());
+    }
+
+    #[test]
+    fn test_arena_errors() {
+        let arena = Arena::with_capacity(16).unwrap();
+        arena.alloc(1u8).unwrap();
+        assert_eq!(
+            arena.alloc([0u8; 16]),
+            Err(ArenaError::CapacityExhausted {
+                requested: 16,
+                remaining: 15
+            })
+        );
+        assert_eq!(Arena::new().alloc(1u8), Err(ArenaError::ZeroCapacity));
+        assert_eq!(
+            Arena::array_layout::<u64>(usize::MAX),
+            Err(ArenaError::LayoutOverflow)
+        );
+        assert!(Box::try_new_in([0u8; 16], &arena).is_err());
+    }
+
+    #[test]
+    fn test_try_alloc_uninit_slice() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let slots = arena.try_alloc_uninit_slice::<u32>(8).unwrap();
+        for (i, slot) in slots.iter_mut().enumerate() {
+            slot.write(i as u32 * 3);
+        }
+        let values = unsafe { slots.assume_init_mut() };
+        assert_eq!(values, &[0, 3, 6, 9, 12, 15, 18, 21]);
+        assert!(arena.try_alloc_uninit_slice::<u32>(16).is_none());
+        assert!(arena.try_alloc_uninit_slice::<u64>(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        let mut arena = Arena::with_capacity(1024).unwrap();
+        arena.alloc([0u8; 700]).unwrap();
+        arena.reset();
+        arena.alloc([0u8; 512]).unwrap();
+        assert_eq!(
+            arena.to_string(),
+            "Arena { used: 512/1024 bytes (50%), peak: 700 }"
+        );
+        assert_eq!(
+            Arena::new().to_string(),
+            "Arena { used: 0/0 bytes (0%), peak: 0 }"
+        );
+    }
+
+    #[test]
+    fn test_reset_zeroing() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc_slice_copy(&[0x5Au8; 40]).unwrap();
+        arena.reset_zeroing();
+        assert_eq!(arena.used(), 0);
+        assert!(unsafe { arena.allocation.slice(0..40) }
+            .iter()
+            .all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_used_bytes() {
+        let mut arena = Arena::with_capacity_aligned(64, 16).unwrap();
+        arena.alloc(0x0102u16).unwrap();
+        arena.alloc_str("hi").unwrap();
+        let bytes = unsafe { arena.used_bytes() };
+        assert_eq!(bytes.len(), 4);
+        assert_eq!(&bytes[..2], &0x0102u16.to_ne_bytes());
+        assert_eq!(&bytes[2..], b"hi");
+
+        // Padding from mixed alignments reads back as zero, which Miri
+        // checks is initialized.
+        arena.alloc(1u8).unwrap();
+        arena.alloc(2u32).unwrap();
+        arena.align_to(16).unwrap();
+        let bytes = unsafe { arena.used_bytes() };
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(&bytes[4..8], [1, 0, 0, 0]);
+        assert_eq!(bytes[8..12], 2u32.to_ne_bytes());
+        assert_eq!(&bytes[12..], [0; 4]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_dump() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc_slice_copy(b"hello, arena!\0\x01\xff").unwrap();
+        arena.alloc_str("tail").unwrap();
+        let mut out = Vec::new();
+        unsafe { arena.dump(&mut out) }.unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "00000000: 68 65 6c 6c 6f 2c 20 61 72 65 6e 61 21 00 01 ff | hello, arena!...\n\
+             00000010: 74 61 69 6c                                     | tail\n"
+        );
+
+        let mut out = Vec::new();
+        unsafe { Arena::new().dump(&mut out) }.unwrap();
+        assert!(out.is_empty());
+
+        // Alignment padding was zeroed when it was skipped.
+        let mut arena = Arena::with_capacity_aligned(64, 4).unwrap();
+        arena.alloc(b'a').unwrap();
+        arena.alloc(u32::from_le_bytes(*b"bcde")).unwrap();
+        let mut out = Vec::new();
+        unsafe { arena.dump(&mut out) }.unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("00000000: 61 00 00 00 62 63 64 65{:24} | a...bcde\n", "")
+        );
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc_str("original").unwrap();
+        let mut fork = arena.clone();
+        assert_eq!(fork.capacity(), 64);
+        assert_eq!(unsafe { fork.used_bytes() }, b"original");
+        fork.alloc_slice_copy(&[0xFFu8; 16]).unwrap();
+        assert_eq!(unsafe { arena.used_bytes() }, b"original");
+        assert_eq!(arena.used(), 8);
+        assert_eq!(fork.used(), 24);
+    }
+
+    #[test]
+    fn test_can_fit_agrees_with_allocate() {
+        #[repr(align(64))]
+        struct Aligned64([u8; 64]);
+
+        for prefix in 0..192 {
+            let arena = Arena::with_capacity(256).unwrap();
+            arena.alloc_slice_copy(&vec![0u8; prefix]).unwrap();
+            let fits = arena.can_fit::<Aligned64>();
+            let allocated = arena.alloc(Aligned64([0; 64]));
+            assert_eq!(fits, allocated.is_ok(), "prefix {prefix}");
+            if let Ok(a) = allocated {
+                assert_eq!(a.0[63], 0);
+                assert_eq!(a as *const Aligned64 as usize % 64, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_padding_for() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        let layout = Layout::new::<u128>();
+        let padding = arena.padding_for(layout).unwrap();
+        let before = arena.used();
+        arena.alloc(2u128).unwrap();
+        assert_eq!(arena.used() - before, padding + layout.size());
+        assert_eq!(arena.padding_for(Layout::new::<[u8; 64]>()), None);
+    }
+
+    #[test]
+    fn test_would_waste() {
+        let arena = Arena::with_capacity_aligned(16, 8).unwrap();
+        assert_eq!(arena.would_waste::<u64>(), 0);
+        arena.alloc(1u8).unwrap();
+        assert_eq!(arena.would_waste::<u64>(), 7);
+        assert_eq!(arena.would_waste::<u16>(), 1);
+        assert_eq!(arena.would_waste::<u8>(), 0);
+        assert_eq!(arena.would_waste::<[u64; 0]>(), 0);
+        arena.alloc(2u64).unwrap();
+        assert_eq!(arena.wasted_padding(), 7);
+    }
+
+    #[test]
+    fn test_allocate_down() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let mut regions = Vec::new();
+        for i in 0..4u64 {
+            let up: *const u64 = arena.alloc(i).unwrap();
+            let down: *const u64 = arena.alloc_down(i + 100).unwrap();
+            assert_eq!(down.addr() % 8, 0);
+            regions.push((up, down));
+        }
+        assert_eq!(arena.used(), 64);
+        assert_eq!(arena.remaining(), 0);
+        assert!(arena.alloc(0u8).is_err());
+        assert!(arena.alloc_down(0u8).is_err());
+        let (last_up, last_down) = regions[3];
+        assert_eq!(last_up.wrapping_add(1), last_down);
+        for (i, (up, down)) in regions.into_iter().enumerate() {
+            unsafe {
+                assert_eq!(*up, i as u64);
+                assert_eq!(*down, i as u64 + 100);
+            }
+        }
+        arena.reset();
+        assert_eq!(arena.remaining(), 64);
+    }
+
+    #[test]
+    fn test_grow_to_moves_down_region() {
+        let mut arena = Arena::with_capacity_aligned(32, 8).unwrap();
+        arena.alloc(1u32).unwrap();
+        arena.alloc_down(2u64).unwrap();
+        arena.grow_to(128).unwrap();
+        assert_eq!(arena.used(), 12);
+        assert!(arena.remaining() >= 128 - 12 - 7);
+        let top = arena.top.get();
+        assert_eq!(
+            unsafe { arena.allocation.slice(top..top + 8) },
+            2u64.to_ne_bytes()
+        );
+        assert_eq!(arena.allocation.len() - top, 8);
+    }
+
+    #[test]
+    fn test_down_region_stays_aligned_on_odd_buffer() {
+        #[repr(align(8))]
+        struct Buffer([u8; 72]);
+
+        let mut buffer = Buffer([0; 72]);
+        // One past an 8-byte boundary, so offsets and addresses disagree.
+        let mut arena = Arena::from_buffer(&mut buffer.0[1..]);
+        arena.alloc(1u8).unwrap();
+        arena.alloc_down(0x0102_0304_0506_0708_u64).unwrap();
+        let read_top = |arena: &Arena<'_>| {
+            let ptr = unsafe { arena.at_offset::<u64>(arena.top.get()) };
+            assert!(ptr.is_aligned());
+            unsafe { *ptr }
+        };
+        let clone = arena.clone();
+        assert_eq!(read_top(&clone), 0x0102_0304_0506_0708);
+        arena.grow_to(256).unwrap();
+        assert_eq!(read_top(&arena), 0x0102_0304_0506_0708);
+        arena.shrink_to_used().unwrap();
+        assert_eq!(read_top(&arena), 0x0102_0304_0506_0708);
+
+        // Buffers from a byte-aligned allocator land at odd addresses too.
+        let outer = Arena::with_capacity_aligned(1024, 8).unwrap();
+        outer.alloc(0u8).unwrap();
+        let mut inner = Arena::with_capacity_in(64, &outer).unwrap();
+        inner.alloc_down(7u64).unwrap();
+        outer.alloc(0u8).unwrap();
+        inner.grow_to(128).unwrap();
+        let ptr = unsafe { inner.at_offset::<u64>(inner.top.get()) };
+        assert!(ptr.is_aligned());
+        assert_eq!(unsafe { *ptr }, 7);
+    }
+
+    #[test]
+    fn test_alloc_bytes() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        let bytes = arena.alloc_bytes(32).unwrap();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            byte.write(i as u8 ^ 0x5a);
+        }
+        let bytes = unsafe { &*(bytes as *const [std::mem::MaybeUninit<u8>] as *const [u8]) };
+        assert!(bytes.iter().enumerate().all(|(i, &b)| b == i as u8 ^ 0x5a));
+        assert_eq!(arena.used(), 33);
+    }
+
+    #[test]
+    fn test_alloc_array() {
+        let arena = Arena::with_capacity_aligned(64, 4).unwrap();
+        // `N` is inferred from the annotation: the length is in the type.
+        let array: &mut [std::mem::MaybeUninit<u32>; 8] = arena.alloc_array().unwrap();
+        for (i, slot) in array.iter_mut().enumerate() {
+            slot.write(i as u32 * 3);
+        }
+        let array = unsafe { &*(array as *const [_; 8]).cast::<[u32; 8]>() };
+        assert_eq!(array.len(), 8);
+        assert_eq!(array[7], 21);
+        assert_eq!(arena.used(), 32);
+        assert!(arena.alloc_array::<u64, 8>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_read_from() {
+        let arena = Arena::with_capacity(16).unwrap();
+        let mut src: &[u8] = b"hello, arena";
+        assert_eq!(arena.read_from(&mut src, 5).unwrap(), b"hello");
+        assert_eq!(src, b", arena");
+
+        let e = arena.read_from(&mut src, 32).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::OutOfMemory);
+        let e = arena.read_from(&mut src, 8).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(arena.used(), 5);
+    }
+
+    #[test]
+    fn test_alloc_unsized() {
+        #[repr(C)]
+        struct Packet {
+            len: u32,
+            body: [u8],
+        }
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let (layout, _) = Layout::new::<u32>()
+            .extend(Layout::array::<u8>(5).unwrap())
+            .unwrap();
+        let ptr = arena
+            .alloc_unsized::<Packet>(layout.pad_to_align(), 5)
+            .unwrap()
+            .as_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).len).write(5);
+            let body = std::ptr::addr_of_mut!((*ptr).body).cast::<u8>();
+            body.copy_from_nonoverlapping(b"hello".as_ptr(), 5);
+        }
+        let packet = unsafe { &*ptr };
+        assert_eq!((packet.len, &packet.body), (5, &b"hello"[..]));
+        assert_eq!(std::mem::size_of_val(packet), 12);
+        assert_eq!(arena.used(), 12);
+    }
+
+    #[test]
+    fn test_alloc_uninit() {
+        struct Header {
+            len: u32,
+            tag: u8,
+        }
+        let arena = Arena::with_capacity(64).unwrap();
+        let slot = arena.alloc_uninit::<Header>().unwrap();
+        let ptr = slot.as_mut_ptr();
+        unsafe {
+            std::ptr::addr_of_mut!((*ptr).len).write(12);
+            std::ptr::addr_of_mut!((*ptr).tag).write(3);
+        }
+        let header = unsafe { slot.assume_init_mut() };
+        assert_eq!((header.len, header.tag), (12, 3));
+
+        let slice = arena.alloc_uninit_slice::<u16>(4).unwrap();
+        for (i, slot) in slice.iter_mut().enumerate() {
+            slot.write(i as u16);
+        }
+        assert_eq!(unsafe { slice.assume_init_ref() }, &[0, 1, 2, 3]);
+        assert!(arena.alloc_uninit_slice::<u64>(8).is_err());
+    }
+
+    #[test]
+    fn test_generations() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        let mut values = Vec::new();
+        for g in 0..3u32 {
+            assert_eq!(arena.new_generation(), g);
+            values.push(arena.alloc([g as u8; 8]).unwrap() as *const [u8; 8]);
+        }
+        assert_eq!(arena.used(), 24);
+        arena.reset_to_generation(2);
+        assert_eq!(arena.used(), 16);
+        assert_eq!(unsafe { (*values[0], *values[1]) }, ([0; 8], [1; 8]));
+        // The discarded id is handed out again.
+        assert_eq!(arena.new_generation(), 2);
+        arena.reset_to_generation(1);
+        assert_eq!(arena.used(), 8);
+        assert_eq!(arena.new_generation(), 1);
+
+        // A scope pushed inside a generation is discarded with it, so
+        // popping it later cannot move the offset forward.
+        arena.reset();
+        arena.alloc(1u8).unwrap();
+        arena.push_scope();
+        let generation = arena.new_generation();
+        arena.alloc([0u8; 4]).unwrap();
+        arena.push_scope();
+        arena.alloc([0u8; 8]).unwrap();
+        arena.reset_to_generation(generation);
+        assert_eq!(*arena.scopes.borrow(), [1]);
+        assert_eq!(arena.check_invariants(), Ok(()));
+        arena.pop_scope();
+        assert_eq!(arena.used(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no generation 0")]
+    fn test_reset_to_unknown_generation() {
+        Arena::with_capacity(8).unwrap().reset_to_generation(0);
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.push_scope();
+        arena.alloc([0u8; 8]).unwrap();
+        let outer = arena.used();
+        arena.push_scope();
+        arena.alloc([0u8; 16]).unwrap();
+        arena.pop_scope();
+        assert_eq!(arena.used(), outer);
+        arena.pop_scope();
+        assert_eq!(arena.used(), 1);
+
+        // Generations started inside the scope end with it.
+        arena.push_scope();
+        arena.alloc([0u8; 4]).unwrap();
+        arena.new_generation();
+        arena.pop_scope();
+        assert!(arena.generations.borrow().is_empty());
+        assert_eq!(arena.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "pop_scope without a matching push_scope")]
+    fn test_unbalanced_pop_scope() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.push_scope();
+        arena.pop_scope();
+        arena.pop_scope();
+    }
+
+    #[test]
+    fn test_align_to() {
+        let arena = Arena::with_capacity(512).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.align_to(256).unwrap();
+        let byte = arena.alloc(2u8).unwrap();
+        assert_eq!(byte as *mut u8 as usize % 256, 0);
+
+        let small = Arena::with_capacity(4).unwrap();
+        small.alloc(1u8).unwrap();
+        assert!(matches!(
+            small.align_to(4096),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+        assert_eq!(small.used(), 1);
+    }
+
+    #[test]
+    fn test_alloc_slice_copy_saturating() {
+        let arena = Arena::with_capacity_aligned(16, 8).unwrap();
+        let src: Vec<u32> = (0..10).collect();
+        let written = arena.alloc_slice_copy_saturating(&src);
+        assert_eq!(written, &[0, 1, 2, 3]);
+        assert!(arena.alloc_slice_copy_saturating(&src).is_empty());
+        assert_eq!(arena.used(), 16);
+    }
+
+    #[test]
+    fn test_shrink_in_place() {
+        let arena = Arena::with_capacity(128).unwrap();
+        let mut v: Vec<u32, _> = Vec::with_capacity_in(16, &arena);
+        v.extend(0..5);
+        let _after = Box::new_in(0u8, &arena);
+        let ptr = v.as_ptr();
+        let (used, wasted) = (arena.used(), arena.wasted_padding());
+        v.shrink_to_fit();
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(v.capacity(), 5);
+        assert_eq!(v, [0, 1, 2, 3, 4]);
+        // The freed tail is stuck behind `_after`.
+        assert_eq!(arena.used(), used);
+        assert_eq!(arena.wasted_padding(), wasted + 11 * 4);
+    }
+
+    #[test]
+    fn test_shrink_accounting() {
+        // The last block gives its tail back.
+        let arena = Arena::with_capacity_aligned(256, 8).unwrap();
+        let mut last: Vec<u32, _> = Vec::with_capacity_in(16, &arena);
+        last.extend(0..4);
+        let (used, wasted) = (arena.used(), arena.wasted_padding());
+        last.shrink_to_fit();
+        assert_eq!(arena.used(), used - 12 * 4);
+        assert_eq!(arena.wasted_padding(), wasted);
+
+        // A block in the middle keeps its place and the tail is dead space.
+        let mut middle: Vec<u32, _> = Vec::with_capacity_in(8, &arena);
+        middle.push(1);
+        let _after = Box::new_in(0u8, &arena);
+        let (used, wasted) = (arena.used(), arena.wasted_padding());
+        middle.shrink_to_fit();
+        assert_eq!(arena.used(), used);
+        assert_eq!(arena.wasted_padding(), wasted + 7 * 4);
+
+        // A stricter alignment moves the block, leaving all of the old one.
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(0u8).unwrap();
+        let old_layout = Layout::new::<[u8; 16]>();
+        let old = (&arena).allocate(old_layout).unwrap().cast::<u8>();
+        unsafe {
+            old.as_ptr()
+                .copy_from_nonoverlapping([7u8; 16].as_ptr(), 16)
+        };
+        arena.alloc(0u8).unwrap();
+        assert_eq!(arena.wasted_padding(), 0);
+        let new_layout = Layout::new::<u64>();
+        let new = unsafe { (&arena).shrink(old, old_layout, new_layout) }.unwrap();
+        assert_eq!(arena.offset_of(new.cast().as_ptr()), Some(24));
+        assert_eq!(unsafe { new.as_ref() }, [7; 8]);
+        assert_eq!(arena.used(), 32);
+        assert_eq!(arena.wasted_padding(), 6 + 16);
+    }
+
+    #[test]
+    fn test_capacity_for() {
+        type Node = (u64, u32);
+        let arena = Arena::with_capacity(Arena::capacity_for::<Node>(100)).unwrap();
+        for i in 0..100 {
+            arena.alloc::<Node>((i, 0)).unwrap();
+        }
+        assert!(!arena.can_fit::<Node>());
+        assert_eq!(Arena::capacity_for::<u64>(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn test_into_used() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc_slice_copy(b"hello ").unwrap();
+        arena.alloc_str("arena").unwrap();
+        let used = unsafe { arena.into_used() };
+        assert_eq!(&used[..], b"hello arena");
+
+        let arena = Arena::with_capacity_aligned(16, 16).unwrap();
+        arena.alloc(7u8).unwrap();
+        assert_eq!(arena.into_boxed_uninit().len(), 16);
+
+        // The buffer starts uninitialized; Miri checks the padding between
+        // the blocks was written.
+        let arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.alloc(2u32).unwrap();
+        arena.alloc(3u16).unwrap();
+        arena.alloc(4u64).unwrap();
+        let used = unsafe { arena.into_used() };
+        assert_eq!(used.len(), 24);
+        assert_eq!(used[..4], [1, 0, 0, 0]);
+        assert_eq!(used[4..8], 2u32.to_ne_bytes());
+        assert_eq!(used[8..10], 3u16.to_ne_bytes());
+        assert_eq!(used[10..16], [0; 6]);
+        assert_eq!(used[16..], 4u64.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_with_capacity_in() {
+        let outer = Arena::with_capacity_aligned(1024, 8).unwrap();
+        let mut inner = Arena::with_capacity_in(256, &outer).unwrap();
+        assert_eq!(outer.used(), 256);
+        let value = inner.alloc(7u64).unwrap() as *const u64;
+        assert!(outer.owns(value.cast()));
+        assert_eq!(unsafe { *value }, 7);
+
+        inner.grow_to(512).unwrap();
+        assert_eq!(inner.capacity(), 512);
+        assert_eq!(unsafe { inner.used_bytes() }.len(), 8);
+        assert!(outer.owns(unsafe { inner.used_bytes() }.as_ptr()));
+        // The new buffer is the outer arena's last block, so dropping the
+        // inner arena hands it back.
+        drop(inner);
+        assert_eq!(outer.used(), 256);
+        assert!(Arena::with_capacity_in(1024, &outer).is_err());
+    }
+
+    #[test]
+    fn test_from_boxed_slice() {
+        let buf = vec![0u8; 256].into_boxed_slice();
+        let start = buf.as_ptr();
+        let arena = Arena::from_boxed_slice(buf);
+        assert_eq!(arena.capacity(), 256);
+        assert_eq!(*arena.alloc(5u32).unwrap(), 5);
+        let buf = unsafe { arena.into_boxed_slice() };
+        assert_eq!(buf.as_ptr(), start);
+
+        let arena = Arena::from_boxed_slice(buf);
+        assert_eq!(arena.used(), 0);
+        assert!(arena.owns(start));
+        assert_eq!(arena.alloc_str("again").unwrap(), "again");
+        drop(Arena::from_boxed_slice(Box::default()));
+    }
+
+    #[test]
+    fn test_boxed_uninit() {
+        // Nothing here reads a byte before it is written, which Miri checks.
+        let arena = Arena::from_boxed_uninit(Box::new_uninit_slice(64));
+        let block = (&arena).allocate(Layout::new::<[u8; 3]>()).unwrap();
+        unsafe { block.cast::<[u8; 3]>().write([1, 2, 3]) };
+        arena.alloc(7u8).unwrap();
+        let buf = arena.into_boxed_uninit();
+        assert_eq!(buf.len(), 64);
+        let written: Vec<u8> = buf[..4]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        assert_eq!(written, [1, 2, 3, 7]);
+
+        // Aligned storage is copied, uninitialized bytes and all.
+        let arena = Arena::with_capacity_aligned(32, 8).unwrap();
+        arena.alloc(9u64).unwrap();
+        let buf = arena.into_boxed_uninit();
+        let first = unsafe { buf.as_ptr().cast::<u64>().read_unaligned() };
+        assert_eq!((buf.len(), first), (32, 9));
+    }
+
+    #[test]
+    fn test_offset_round_trip() {
+        let arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        let value = arena.alloc(0xdead_beef_u32).unwrap() as *mut u32;
+        let offset = arena.offset_of(value.cast()).unwrap();
+        assert_eq!(unsafe { arena.at_offset::<u32>(offset) }, value);
+        assert_eq!(unsafe { *arena.at_offset::<u32>(offset) }, 0xdead_beef);
+        assert_eq!(arena.offset_of(&0u8), None);
+    }
+
+    #[test]
+    fn test_node_refs_survive_growth() {
+        struct Node {
+            value: u32,
+            next: Option<NodeRef<Node>>,
+        }
+
+        let mut arena = Arena::with_capacity_aligned(128, 8).unwrap();
+        let mut head = None;
+        for value in [3, 2, 1] {
+            head = Some(arena.alloc_node(Node { value, next: head }).unwrap());
+        }
+        arena.grow_to(4096).unwrap();
+        arena.get_mut(head.unwrap()).unwrap().value = 10;
+
+        let mut values = Vec::new();
+        let mut cursor = head;
+        while let Some(node) = cursor {
+            let node = arena.get(node).unwrap();
+            values.push(node.value);
+            cursor = node.next;
+        }
+        assert_eq!(values, [10, 2, 3]);
+
+        let last = arena.alloc_node(0u64).unwrap();
+        arena.reset();
+        assert!(arena.get(last).is_none());
+    }
+
+    #[test]
+    fn test_zero_sized_nodes() {
+        let mut arena = Arena::with_capacity(16).unwrap();
+        arena.alloc(1u8).unwrap();
+        let unit = arena.alloc_node(()).unwrap();
+        assert_eq!(unit.offset(), 1);
+        assert_eq!(arena.get(unit), Some(&()));
+        let node = arena.alloc_node(2u8).unwrap();
+        assert_eq!(node.offset(), 1);
+        assert_eq!(arena.get(node), Some(&2));
+    }
+
+    #[test]
+    fn test_stale_node_refs() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let first = arena.alloc_node(1u64).unwrap();
+        arena.reset();
+        // Back in bounds and at the same offset, but a different value.
+        let second = arena.alloc_node(2u64).unwrap();
+        assert_eq!(first.offset(), second.offset());
+        assert_ne!(first, second);
+        assert!(arena.get(first).is_none());
+        assert_eq!(arena.get(second), Some(&2));
+
+        // Rewinding above the node keeps it, as do popping the last block
+        // and a failed read.
+        let mark = arena.checkpoint();
+        arena.alloc(0u8).unwrap();
+        unsafe { arena.rewind(mark) };
+        let byte = arena.alloc(0u8).unwrap() as *const u8;
+        unsafe { arena.pop(byte, Layout::new::<u8>()) };
+        #[cfg(feature = "std")]
+        assert!(arena.read_from(&mut &b"ab"[..], 4).is_err());
+        assert_eq!(arena.position(), 8);
+        assert_eq!(arena.get(second), Some(&2));
+
+        // Rewinding over a node, even partly, drops the whole epoch.
+        let third = arena.alloc_node(3u32).unwrap();
+        unsafe { arena.set_offset(third.offset() + 2) };
+        assert!(arena.get(second).is_none());
+        assert!(arena.get(third).is_none());
+    }
+
+    #[test]
+    fn test_reserve() {
+        let arena = Arena::with_capacity(24).unwrap();
+        assert_eq!(
+            arena.reserve(1000),
+            Err(ArenaError::CapacityExhausted {
+                requested: 1000,
+                remaining: 24
+            })
+        );
+        assert_eq!(arena.reserve(24), Ok(()));
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_reserve_for() {
+        let mut arena = Arena::with_capacity(16).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.reserve_for::<u64>(1000).unwrap();
+        assert!(arena.capacity() >= 8000);
+        for i in 0..1000u64 {
+            arena.alloc(i).unwrap();
+        }
+        let capacity = arena.capacity();
+        arena.reserve_for::<u64>(0).unwrap();
+        assert_eq!(arena.capacity(), capacity);
+        assert!(arena.reserve_for::<u64>(usize::MAX).is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "alignment must be a power of two")]
+    fn test_degenerate_alignment_asserts() {
+        padding_for_parts(&[0; 16], 0, 4, 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "size exceeds isize::MAX")]
+    fn test_degenerate_size_asserts() {
+        padding_for_parts(&[0; 16], 0, usize::MAX, 1);
+    }
+
+    #[test]
+    fn test_fill_remaining() {
+        let mut arena = Arena::with_capacity(16).unwrap();
+        arena.fill_remaining(0xFF);
+        assert_eq!(arena.used(), 0);
+        *arena.alloc(0u8).unwrap() = 1;
+        let next = arena.alloc_uninit::<u8>().unwrap();
+        assert_eq!(unsafe { next.assume_init() }, 0xFF);
+        assert_eq!(unsafe { arena.used_bytes() }, [1, 0xFF]);
+    }
+
+    #[test]
+    fn test_prefault() {
+        let page = crate::page_size();
+        let arena = Arena::with_capacity(4 * page + 100).unwrap();
+        let kept = arena.alloc(0xABu8).unwrap();
+        arena.prefault();
+        assert_eq!(*kept, 0xAB);
+        assert_eq!(arena.used(), 1);
+        let bulk = arena
+            .alloc_from_iter(std::iter::repeat_n(1u8, 4 * page))
+            .unwrap();
+        assert!(bulk.iter().all(|&b| b == 1));
+        Arena::with_capacity(0).unwrap().prefault();
+    }
+
+    #[test]
+    fn test_alloc_slice_with() {
+        let arena = Arena::with_capacity(128).unwrap();
+        let squares = arena.alloc_slice_with(10, |i| (i * i) as u32).unwrap();
+        assert_eq!(squares, [0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+        assert!(arena.alloc_slice_with::<u64>(100, |i| i as u64).is_err());
+        assert!(arena.alloc_slice_with(0, |_| 0u8).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_alloc_slice_with_panic_drops_prefix() {
+        use std::{cell::Cell, panic, rc::Rc};
+
+        struct Counted(Rc<Cell<usize>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let arena = Arena::with_capacity(256).unwrap();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            arena.alloc_slice_with(10, |i| {
+                assert!(i < 4, "boom");
+                Counted(drops.clone())
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_alloc_slice_clone() {
+        let arena = Arena::with_capacity(256).unwrap();
+        let names = ["ada".to_string(), "grace".to_string()];
+        let copy = arena.alloc_slice_clone(&names).unwrap();
+        copy[0].push_str(" lovelace");
+        assert_eq!(copy, ["ada lovelace", "grace"]);
+        assert_eq!(names[0], "ada");
+        // The strings own heap buffers; drop them before the arena goes.
+        unsafe { std::ptr::drop_in_place(copy) };
+    }
+
+    #[test]
+    fn test_alloc_slice_clone_panic_drops_prefix() {
+        use std::{cell::Cell, panic, rc::Rc};
+
+        struct Fragile(usize, Rc<Cell<usize>>);
+        impl Clone for Fragile {
+            fn clone(&self) -> Self {
+                assert!(self.0 != 2, "boom");
+                Fragile(self.0, self.1.clone())
+            }
+        }
+        impl Drop for Fragile {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let src: Vec<_> = (0..5).map(|i| Fragile(i, drops.clone())).collect();
+        let arena = Arena::with_capacity(256).unwrap();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            arena.alloc_slice_clone(&src).map(|_| ())
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_alloc_from_iter() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let squares = arena.alloc_from_iter((0..10u32).map(|i| i * i)).unwrap();
+        assert_eq!(squares, [0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+        let evens = arena
+            .alloc_from_iter((0..10u8).filter(|i| i % 2 == 0))
+            .unwrap();
+        assert_eq!(evens, [0, 2, 4, 6, 8]);
+        assert!(arena
+            .alloc_from_iter(std::iter::empty::<u64>())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_alloc_from_iter_overflow() {
+        let arena = Arena::with_capacity(16).unwrap();
+        arena.alloc(1u8).unwrap();
+        let before = arena.stats();
+        let lifetime = arena.lifetime_allocated();
+        assert!(matches!(
+            arena.alloc_from_iter(0..5u64),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+        assert_eq!(arena.stats(), before);
+
+        // No size hint, so this only fails once the arena is full.
+        let drops = std::rc::Rc::new(());
+        let unbounded = (0..).map(|_| drops.clone()).take_while(|_| true);
+        assert!(arena.alloc_from_iter(unbounded).is_err());
+        assert_eq!(std::rc::Rc::strong_count(&drops), 1);
+        assert_eq!(arena.stats(), before);
+        assert_eq!(arena.lifetime_allocated(), lifetime);
+    }
+
+    #[test]
+    fn test_reset_to_persistent() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        let config = arena.alloc(42u64).unwrap() as *const u64;
+        arena.mark_persistent();
+        let mut frame_start = None;
+        for frame in 0..5u32 {
+            let temp = arena.alloc_slice_copy(&[frame; 8]).unwrap();
+            assert_eq!(*frame_start.get_or_insert(temp.as_ptr()), temp.as_ptr());
+            arena.reset_to_persistent();
+            assert_eq!(arena.used(), 8);
+        }
+        assert_eq!(unsafe { *config }, 42);
+        arena.reset();
+        arena.reset_to_persistent();
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_align_padding_matches_modulo() {
+        for align in (0..12).map(|shift| 1usize << shift) {
+            for addr in (0..4096).chain(usize::MAX - 4096..=usize::MAX) {
+                let modulo = (align - addr % align) % align;
+                assert_eq!(
+                    align_padding(addr, align),
+                    modulo,
+                    "addr {addr}, align {align}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_fast_path_matches_general_padding() {
+        let arena = Arena::with_capacity_aligned(16, 4).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.alloc_down(2u32).unwrap();
+        for size in 0..=16 {
+            let layout = Layout::array::<u8>(size).unwrap();
+            let general = padding_for_parts(
+                arena.allocation.prefix(arena.top.get()),
+                arena.offset.get(),
+                size,
+                1,
+            );
+            assert_eq!(arena.padding(layout), general, "size {size}");
+            assert_eq!(arena.can_fit_slice::<u8>(size), general.is_some());
+        }
+        assert_eq!(arena.alloc_slice_copy(&[7u8; 11]).unwrap().len(), 11);
+        assert!(arena.alloc(0u8).is_err());
+    }
+
+    #[test]
+    fn test_with_capacity_zeroed() {
+        let mut arena = Arena::with_capacity_zeroed(256).unwrap();
+        let block = (&arena).allocate_zeroed(Layout::new::<[u8; 32]>()).unwrap();
+        assert!(unsafe { block.as_ref() }.iter().all(|&b| b == 0));
+        assert_eq!(arena.zeroed.get(), (32, 256));
+        arena.alloc_slice_copy(&[0xFFu8; 8]).unwrap();
+        arena.reset();
+        let block = (&arena).allocate_zeroed(Layout::new::<[u8; 64]>()).unwrap();
+        assert!(unsafe { block.as_ref() }.iter().all(|&b| b == 0));
+        arena.reset();
+        // Poisoning overwrites what the reset reclaimed.
+        #[cfg(not(feature = "poison"))]
+        assert!(unsafe { arena.into_boxed_slice() }.iter().all(|&b| b == 0));
+
+        let fresh = Arena::with_capacity_zeroed(4096).unwrap();
+        assert!(unsafe { fresh.into_boxed_slice() }.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_alloc_rows() {
+        let arena = Arena::with_capacity(256).unwrap();
+        arena.alloc(1u8).unwrap();
+        let (grid, stride) = arena.alloc_rows::<f32>(3, 5, 32).unwrap();
+        assert_eq!(stride, 8);
+        assert_eq!(grid.len(), 2 * 8 + 5);
+        for row in 0..3 {
+            assert_eq!(grid[row * stride..].as_ptr() as usize % 32, 0);
+        }
+        grid[2 * stride + 4] = 1.0;
+
+        let (rgb, stride) = arena.alloc_rows::<[u8; 3]>(2, 5, 16).unwrap();
+        assert_eq!(stride, 16);
+        assert_eq!(rgb[stride..].as_ptr() as usize % 16, 0);
+    }
+
+    #[test]
+    fn test_drop_over_aligned() {
+        for capacity in [0, 1, 100, 4096] {
+            let arena = Arena::with_capacity_aligned(capacity, 256).unwrap();
+            assert_eq!(arena.allocation.as_ptr() as usize % 256, 0);
+            if capacity != 0 {
+                arena.alloc(1u8).unwrap();
+            }
+            drop(arena);
+        }
+        let mut arena = Arena::with_capacity_aligned(64, 64).unwrap();
+        arena.alloc(5u64).unwrap();
+        arena.grow_to(512).unwrap();
+        assert_eq!(arena.allocation.as_ptr() as usize % 64, 0);
+    }
+
+    #[test]
+    fn test_is_empty_is_full() {
+        let mut arena = Arena::with_capacity_aligned(16, 8).unwrap();
+        assert!(arena.is_empty() && !arena.is_full());
+        arena.alloc(1u64).unwrap();
+        assert!(!arena.is_empty() && !arena.is_full());
+        arena.alloc_down(2u64).unwrap();
+        assert!(arena.is_full());
+        arena.reset();
+        assert!(arena.is_empty() && !arena.is_full());
+        assert!(Arena::new().is_empty() && Arena::new().is_full());
+    }
+
+    #[test]
+    fn test_grow_middle_vec() {
+        let arena = Arena::with_capacity_aligned(256, 8).unwrap();
+        let mut v = Vec::with_capacity_in(4, &arena);
+        v.extend([1u32, 2, 3, 4]);
+        let after = arena.alloc(0u8).unwrap();
+        let wasted = arena.wasted_padding();
+        v.push(5);
+        assert_eq!(v, [1, 2, 3, 4, 5]);
+        assert_eq!(*after, 0);
+        // The old 16-byte buffer is dead, plus the padding after `after`.
+        assert_eq!(arena.wasted_padding(), wasted + 16 + 3);
+        assert_eq!(arena.used(), 16 + 1 + 3 + 4 * 8);
+    }
+
+    #[test]
+    fn test_wasted_padding() {
+        let mut arena = Arena::with_capacity_aligned(64, 16).unwrap();
+        arena.alloc(1u8).unwrap();
+        assert_eq!(arena.wasted_padding(), 0);
+        arena.alloc(2u128).unwrap();
+        assert_eq!(arena.wasted_padding(), 15);
+        arena.alloc_down(3u8).unwrap();
+        arena.alloc_down(4u32).unwrap();
+        assert_eq!(arena.wasted_padding(), 15 + 3);
+        arena.reset();
+        assert_eq!(arena.wasted_padding(), 0);
+    }
+
+    #[test]
+    fn test_shrink_last_block_reclaims_tail() {
+        let arena = Arena::with_capacity(256).unwrap();
+        let mut v: Vec<u32, _> = Vec::new_in(&arena);
+        v.extend(0..40);
+        let before = arena.used();
+        v.truncate(10);
+        v.shrink_to_fit();
+        assert_eq!(arena.used(), before - 30 * 4);
+        assert_eq!(v, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut arena = Arena::with_capacity(256).unwrap();
+        let mut state = 0x2545_f491_u32;
+        let mut markers = Vec::new();
+        for _ in 0..2000 {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let n = (state >> 8) as usize % 24;
+            match state % 8 {
+                0 | 1 => drop(arena.alloc_slice_copy(&[0u8; 24][..n])),
+                2 => drop(arena.alloc(n as u64)),
+                3 => drop(arena.alloc_down(n as u32)),
+                4 => markers.push(arena.checkpoint()),
+                5 => {
+                    if let Some(marker) = markers.pop() {
+                        if marker.0 <= arena.position() {
+                            unsafe { arena.rewind(marker) };
+                        }
+                    }
+                }
+                6 => arena.push_scope(),
+                _ => {
+                    if n == 0 {
+                        arena.reset();
+                        markers.clear();
+                    } else if !arena.scopes.borrow().is_empty() {
+                        arena.pop_scope();
+                    }
+                }
+            }
+            assert_eq!(arena.check_invariants(), Ok(()));
+        }
+
+        // Rewinding below a scope drops it, and freeing the block under one
+        // keeps the block.
+        arena.reset();
+        let mark = arena.checkpoint();
+        arena.alloc(1u32).unwrap();
+        arena.push_scope();
+        arena.new_generation();
+        unsafe { arena.rewind(mark) };
+        assert_eq!(arena.check_invariants(), Ok(()));
+        assert!(arena.scopes.borrow().is_empty());
+        assert!(arena.generations.borrow().is_empty());
+        let block = Box::new_in(2u32, &arena);
+        let used = arena.used();
+        arena.push_scope();
+        drop(block);
+        assert_eq!(arena.used(), used);
+        assert_eq!(arena.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.alloc(2u64).unwrap();
+        arena.alloc(()).unwrap();
+        arena.alloc_down(3u32).unwrap();
+        assert_eq!(
+            arena.stats(),
+            Stats {
+                capacity: 64,
+                used: 20,
+                remaining: 44,
+                peak: 20,
+                wasted: 7,
+                allocations: 3,
+            }
+        );
+        arena.reset();
+        assert_eq!(arena.stats().allocations, 0);
+        assert_eq!(arena.stats().peak, 20);
+    }
+
+    #[test]
+    fn test_patch_header_at_position() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        let header = arena.position();
+        arena.alloc_slice_copy(&[0u8; 4]).unwrap();
+        arena.alloc_str("records").unwrap();
+        let len = (arena.position() - header) as u32;
+        unsafe { arena.slice_at(header, 4) }.copy_from_slice(&len.to_le_bytes());
+        assert_eq!(&unsafe { arena.used_bytes() }[..4], 11u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<Arena<'static>>();
+        assert_send::<ChunkedArena>();
+        assert_send::<TypedArena<String>>();
+        assert_send::<SyncArena>();
+        assert_sync::<SyncArena>();
+        assert_send::<&SyncArena>();
+        assert_sync::<&SyncArena>();
+
+        let arena = Arena::with_capacity_aligned(16, 8).unwrap();
+        arena.alloc(7u32).unwrap();
+        let arena = std::thread::spawn(move || arena).join().unwrap();
+        assert_eq!(arena.used(), 4);
+    }
+
+    #[test]
+    fn test_alloc_tracked_offsets_are_deterministic() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        let run = |arena: &Arena| {
+            let (a, _) = arena.alloc_tracked(1u8).unwrap();
+            let (b, node) = arena.alloc_tracked([2u32; 3]).unwrap();
+            node[1] = 5;
+            assert_eq!(unsafe { *arena.at_offset::<[u32; 3]>(b) }, [2, 5, 2]);
+            (a, b)
+        };
+        let first = run(&arena);
+        arena.reset();
+        assert_eq!(run(&arena), first);
+    }
+
+    #[test]
+    fn test_can_fit_layout() {
+        let arena = Arena::with_capacity(40).unwrap();
+        for _ in 0..4 {
+            assert_eq!(
+                arena.can_fit_layout(Layout::new::<u128>()),
+                arena.can_fit::<u128>()
+            );
+            let _ = arena.alloc(1u128);
+        }
+        assert!(!arena.can_fit_layout(Layout::new::<u128>()));
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        let before = arena.stats();
+        let result = arena.transaction(|arena| {
+            arena.alloc(0u32)?;
+            arena.alloc_down(0u64)?;
+            std::mem::forget(arena.boxed(0u16)?);
+            arena.mark_persistent();
+            arena.push_scope();
+            arena.alloc([0u8; 64]).map(|_| ())
+        });
+        assert!(matches!(result, Err(ArenaError::CapacityExhausted { .. })));
+        let after = arena.stats();
+        assert_eq!(
+            Stats {
+                peak: before.peak,
+                ..after
+            },
+            before
+        );
+        assert_eq!(arena.reset_checked(), Ok(()));
+
+        arena.alloc(1u8).unwrap();
+        let (a, b) = arena
+            .transaction(|arena| {
+                Ok::<_, ArenaError>((arena.alloc_node(2u8)?, arena.alloc_node(3u8)?))
+            })
+            .unwrap();
+        assert_eq!(arena.get(a), Some(&2));
+        assert_eq!(arena.get(b), Some(&3));
+        assert_eq!(arena.used(), 3);
+    }
+
+    #[test]
+    fn test_boxed() {
+        let arena = Arena::with_capacity_aligned(8, 8).unwrap();
+        let err = arena.boxed(String::from("boxed")).unwrap_err();
+        assert!(matches!(err, ArenaError::CapacityExhausted { .. }));
+        let mut b = arena.boxed(5u32).unwrap();
+        *b += 1;
+        assert_eq!(*b, 6);
+        assert!(arena.boxed(1u64).is_err());
+        drop(b);
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_zero_size_layout_in_full_arena() {
+        let arena = Arena::with_capacity(8).unwrap();
+        arena.alloc([0u8; 8]).unwrap();
+        let layout = Layout::from_size_align(0, 64).unwrap();
+        for ptr in [
+            (&arena).allocate(layout).unwrap(),
+            arena.allocate_down(layout).unwrap(),
+        ] {
+            let addr = ptr.cast::<u8>().as_ptr();
+            assert_eq!(addr as usize % 64, 0);
+            assert!(!arena.owns(addr));
+        }
+        assert!(arena.alloc_uninit_slice::<u64>(0).is_ok());
+        assert!(arena.is_full());
+    }
+
+    #[test]
+    fn test_clear_and_grow() {
+        let mut arena = Arena::with_capacity(24).unwrap();
+        arena.alloc([1u8; 20]).unwrap();
+        arena.clear_and_grow(4096).unwrap();
+        assert_eq!(arena.capacity(), 4096);
+        assert_eq!(arena.used(), 0);
+        assert!(arena.can_fit::<[u8; 4096]>());
+        arena.clear_and_grow(16).unwrap();
+        assert_eq!(arena.capacity(), 4096);
+    }
+
+    #[test]
+    fn test_reset_and_right_size() {
+        let mut arena = Arena::with_capacity_aligned(4096, 16).unwrap();
+        arena.alloc([1u8; 100]).unwrap();
+        arena.reset_and_right_size(0.5).unwrap();
+        assert_eq!(arena.capacity(), 150);
+        assert_eq!(arena.backing_layout().align(), 16);
+        assert_eq!(arena.used(), 0);
+        assert!(arena.can_fit::<[u8; 150]>());
+        // Already right-sized: nothing changes.
+        arena.reset_and_right_size(0.5).unwrap();
+        assert_eq!(arena.capacity(), 150);
+    }
+
+    #[test]
+    #[cfg(feature = "tracking")]
+    fn test_dealloc_count() {
+        let arena = Arena::with_capacity(64).unwrap();
+        drop(Box::new_in(1u32, &arena));
+        assert_eq!(arena.dealloc_count(), 1);
+
+        // Growing moves the buffer without going through `deallocate`.
+        let mut v: Vec<u8, _> = Vec::with_capacity_in(2, &arena);
+        let _after = arena.alloc(0u8).unwrap();
+        v.extend(0..8);
+        assert_eq!(arena.dealloc_count(), 1);
+        drop(v);
+        assert_eq!(arena.dealloc_count(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "tracking")]
+    fn test_alignment_histogram() {
+        let mut arena = Arena::with_capacity(256).unwrap();
+        for _ in 0..3 {
+            arena.alloc(1u8).unwrap();
+        }
+        arena.alloc(2u32).unwrap();
+        arena.alloc_down(3u32).unwrap();
+        arena.alloc(4u128).unwrap();
+        let histogram: Vec<_> = arena.alignment_histogram().into_iter().collect();
+        let u128_align = std::mem::align_of::<u128>();
+        assert_eq!(histogram, [(1, 3), (4, 2), (u128_align, 1)]);
+
+        arena.reset();
+        assert!(arena.alignment_histogram().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "tracking")]
+    fn test_allocations_iter() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        arena.alloc(1u8).unwrap();
+        arena.alloc(2u64).unwrap();
+        arena.alloc_down(3u32).unwrap();
+        let sizes: Vec<_> = unsafe { arena.allocations_iter() }
+            .map(<[u8]>::len)
+            .collect();
+        assert_eq!(sizes, [1, 8, 4]);
+
+        let mut v: Vec<u16, _> = Vec::with_capacity_in(2, &arena);
+        v.extend([1, 2, 3]);
+        drop(v);
+        assert_eq!(unsafe { arena.allocations_iter() }.count(), 3);
+
+        arena.grow_to(128).unwrap();
+        let last = unsafe { arena.allocations_iter() }.last().unwrap();
+        assert_eq!(last, 3u32.to_ne_bytes());
+
+        arena.reset();
+        assert_eq!(unsafe { arena.allocations_iter() }.count(), 0);
+    }
+
+    #[test]
+    fn test_alloc_aligned_slice() {
+        let arena = Arena::with_capacity(512).unwrap();
+        arena.alloc(1u8).unwrap();
+        let slice = arena.alloc_aligned_slice::<u64>(10, 128).unwrap();
+        assert_eq!(slice.as_ptr() as usize % 128, 0);
+        assert_eq!(slice, [0; 10]);
+        let bytes = arena.alloc_aligned_slice::<u32>(3, 1).unwrap();
+        assert_eq!(bytes.as_ptr() as usize % 4, 0);
+    }
+
+    #[test]
+    fn test_remaining_after_align() {
+        let arena = Arena::with_capacity_aligned(32, 8).unwrap();
+        arena.alloc([0u8; 3]).unwrap();
+        assert_eq!(arena.remaining_after_align::<u64>(), arena.remaining() - 5);
+        assert_eq!(arena.remaining_after_align::<u8>(), arena.remaining());
+        arena.alloc([0u8; 26]).unwrap();
+        assert_eq!(arena.remaining_after_align::<u64>(), 0);
+
+        let arena = Arena::builder()
+            .capacity(64)
+            .base_align(16)
+            .granularity(16)
+            .build()
+            .unwrap();
+        arena.alloc(1u8).unwrap();
+        assert_eq!(arena.remaining_after_align::<u8>(), 48);
+        assert_eq!(arena.remaining_after_align::<u64>(), 48);
+    }
+
+    #[test]
+    fn test_copy_into() {
+        let mut src = Arena::with_capacity(1024).unwrap();
+        src.alloc_from_iter(0..100u8).unwrap();
+        let mut dst = Arena::with_capacity(128).unwrap();
+        src.copy_into(&mut dst).unwrap();
+        assert_eq!(unsafe { dst.used_bytes() }, unsafe { src.used_bytes() });
+        assert!(matches!(
+            src.copy_into(&mut dst),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+    }
+
+    #[test]
+    fn test_append() {
+        let shards: Vec<_> = (0..2u8)
+            .map(|shard| {
+                let arena = Arena::with_capacity(64).unwrap();
+                arena
+                    .alloc_from_iter((0..50).map(|i| shard * 100 + i))
+                    .unwrap();
+                arena
+            })
+            .collect();
+        let mut merged = Arena::with_capacity(256).unwrap();
+        merged.alloc(0xFFu8).unwrap();
+        assert_eq!(merged.append(&shards[0]), Ok(1));
+        assert_eq!(merged.append(&shards[1]), Ok(51));
+        let bytes = unsafe { merged.used_bytes() };
+        assert_eq!(bytes.len(), 101);
+        assert_eq!(
+            (bytes[1], bytes[50], bytes[51], bytes[100]),
+            (0, 49, 100, 149)
+        );
+
+        let mut small = Arena::with_capacity(60).unwrap();
+        small.append(&shards[0]).unwrap();
+        assert!(small.append(&shards[1]).is_err());
+        assert_eq!(small.used(), 50);
+    }
+
+    #[test]
+    // Relies on the global allocator aligning more than asked, which Miri
+    // does not.
+    #[cfg_attr(miri, ignore)]
+    fn test_base_alignment() {
+        let arena = Arena::with_capacity(64).unwrap();
+        assert!(arena.base_alignment() >= core::mem::align_of::<usize>());
+        assert!(arena.base_alignment().is_power_of_two());
+        let aligned = Arena::with_capacity_aligned(64, 4096).unwrap();
+        assert!(aligned.base_alignment() >= 4096);
+        let mut buf = [0u8; 8];
+        let borrowed = Arena::from_buffer(&mut buf[1..]);
+        assert_eq!(
+            borrowed.padding_for(Layout::new::<u16>()),
+            Some(borrowed.base_alignment() % 2)
+        );
+    }
+
+    #[test]
+    fn test_backing_layout() {
+        let mut arena = Arena::with_capacity_aligned(100, 64).unwrap();
+        assert_eq!(arena.backing_layout().size(), arena.capacity());
+        assert_eq!(arena.backing_layout().align(), 64);
+        arena.grow_to(200).unwrap();
+        assert_eq!(
+            arena.backing_layout(),
+            Layout::from_size_align(200, 64).unwrap()
+        );
+        let mut buf = [0u8; 8];
+        assert_eq!(
+            Arena::from_buffer(&mut buf).backing_layout(),
+            Layout::new::<[u8; 8]>()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "poison")]
+    fn test_poison_on_reset() {
+        // Freed bytes are read back through the arena's own pointer, which
+        // outlives the references into them.
+        let mut arena = Arena::with_capacity_aligned(32, 4).unwrap();
+        arena.alloc(0x1234_5678_u32).unwrap();
+        let marker = arena.checkpoint();
+        arena.alloc(7u8).unwrap();
+        let (value, tail) = unsafe { (arena.at_offset::<u32>(0), arena.at_offset::<u8>(4)) };
+        unsafe { arena.rewind(marker) };
+        assert_eq!(unsafe { *tail }, 0xDE);
+        assert_eq!(unsafe { *value }, 0x1234_5678);
+        arena.reset();
+        assert_eq!(unsafe { *value }, 0xDEDE_DEDE);
+
+        arena.alloc(1u8).unwrap();
+        arena.reset_zeroing();
+        assert_eq!(unsafe { *tail.sub(4) }, 0);
+    }
+
+    #[test]
+    fn test_alloc_vec() {
+        let arena = Arena::with_capacity_aligned(40, 4).unwrap();
+        let mut v = arena.alloc_vec::<u32>(10).unwrap();
+        assert_eq!(v.capacity(), 10);
+        v.extend(0..10);
+        assert_eq!(arena.used(), 40);
+        assert!(matches!(
+            arena.alloc_vec::<u32>(1),
+            Err(ArenaError::CapacityExhausted { .. })
+        ));
+        assert_eq!(
+            arena.alloc_vec::<u64>(usize::MAX).unwrap_err(),
+            ArenaError::LayoutOverflow
+        );
+        assert_eq!(v.iter().sum::<u32>(), 45);
+    }
+
+    #[test]
+    #[cfg(feature = "hashbrown")]
+    fn test_hash_map() {
+        let arena = Arena::with_capacity(4096).unwrap();
+        let mut map = arena.hash_map::<u32, &str, hashbrown::DefaultHashBuilder>();
+        assert_eq!(arena.used(), 0);
+        for (i, name) in ["zero", "one", "two", "three", "four", "five"]
+            .iter()
+            .enumerate()
+        {
+            map.insert(i as u32, *name);
+        }
+        assert!(arena.used() > 0);
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.get(&3), Some(&"three"));
+        assert_eq!(map.remove(&0), Some("zero"));
+        assert_eq!(map.get(&0), None);
+    }
+
+    #[test]
+    fn test_next_alignment() {
+        let arena = Arena::with_capacity_aligned(32, 16).unwrap();
+        assert!(arena.next_alignment() >= 16);
+        arena.alloc(1u8).unwrap();
+        assert_eq!(arena.next_alignment(), 1);
+        arena.alloc([0u8; 3]).unwrap();
+        assert_eq!(arena.next_alignment(), 4);
+    }
+
+    #[test]
+    fn test_with_scope() {
+        let mut arena = Arena::with_capacity_aligned(64, 8).unwrap();
+        arena.alloc(1u64).unwrap();
+        let len = arena.with_scope(|arena| arena.alloc_str("temporary").unwrap().len());
+        assert_eq!(len, 9);
+        assert_eq!(arena.used(), 8);
+    }
+
+    #[test]
+    fn test_rc_and_arc() {
+        use std::{rc::Rc, sync::Arc};
+
+        #[repr(align(32))]
+        #[derive(Debug, PartialEq)]
+        struct Wide([u8; 32]);
+
+        let arena = Arena::with_capacity(256).unwrap();
+        arena.alloc(1u8).unwrap();
+        let used = arena.used();
+        let rc = Rc::new_in(Wide([7; 32]), &arena);
+        let rc2 = Rc::clone(&rc);
+        assert_eq!(&*rc as *const Wide as usize % 32, 0);
+        assert_eq!(rc2.0, [7; 32]);
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(rc);
+        drop(rc2);
+        // The control block and value were the last block, so they are
+        // given back, while the padding before them is not.
+        assert!(arena.used() < used + 32);
+
+        let arc = Arc::new_in(String::from("shared"), &arena);
+        let weak = Arc::downgrade(&arc);
+        assert_eq!(*weak.upgrade().unwrap(), "shared");
+        drop(arc);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_fit_boundaries() {
+        fn check<T: Default>() {
+            let size = core::mem::size_of::<T>();
+            let align = core::mem::align_of::<T>();
+            // Starting aligned, then one byte in so padding is needed.
+            for lead in [0, 1] {
+                let padding = (align - lead % align) % align;
+                for (extra, fits) in [(-1isize, false), (0, true), (1, true)] {
+                    let capacity = (lead + padding + size).checked_add_signed(extra).unwrap();
+                    let arena = Arena::with_capacity_aligned(capacity, 16).unwrap();
+                    arena.alloc_slice_copy(&vec![0u8; lead]).unwrap();
+                    let layout = Layout::new::<T>();
+                    assert_eq!(arena.can_fit::<T>(), fits);
+                    assert_eq!(arena.can_fit_layout(layout), fits);
+                    assert_eq!(arena.padding_for(layout), fits.then_some(padding));
+                    assert_eq!(arena.alloc(T::default()).is_ok(), fits);
+                    if fits {
+                        assert_eq!(arena.remaining(), extra as usize);
+                        assert!(!arena.can_fit::<T>() || extra as usize >= size);
+                    }
+                }
+            }
+        }
+        check::<u8>();
+        check::<u32>();
+        check::<u128>();
+    }
+
+    #[test]
+    fn test_alloc_first_fit() {
+        let small = Arena::with_capacity(4).unwrap();
+        let large = Arena::with_capacity(32).unwrap();
+        let arenas = [&small, &large];
+        let value = alloc_first_fit(&arenas, [1u8; 16]).unwrap();
+        assert!(large.owns(value.as_ptr()));
+        assert!(small.owns(alloc_first_fit(&arenas, 2u8).unwrap()));
+        assert!(alloc_first_fit(&arenas, [0u8; 32]).is_none());
+    }
+
+    #[test]
+    fn test_reset_if_unique() {
+        let mut arena = std::rc::Rc::new(Arena::with_capacity_aligned(64, 8).unwrap());
+        arena.alloc(1u64).unwrap();
+        let other = arena.clone();
+        assert!(!reset_if_unique(&mut arena));
+        assert_eq!(arena.used(), 8);
+        drop(other);
+        assert!(reset_if_unique(&mut arena));
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_with_thread_local_arena() {
+        use crate::with_thread_local_arena;
+
+        let first = with_thread_local_arena(256, |arena| {
+            arena.alloc([1u8; 100]).unwrap();
+            (arena.capacity(), arena.used())
+        });
+        assert_eq!(first, (256, 100));
+        let second = with_thread_local_arena(1024, |arena| (arena.capacity(), arena.used()));
+        assert_eq!(second, (256, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_with_thread_local_arena_nested() {
+        use crate::with_thread_local_arena;
+
+        with_thread_local_arena(256, |outer| {
+            let kept = outer.alloc([7u8; 8]).unwrap();
+            let inner_used = with_thread_local_arena(256, |inner| {
+                inner.alloc([0u8; 32]).unwrap();
+                inner.used()
+            });
+            assert_eq!(inner_used, 40);
+            assert_eq!(outer.used(), 8);
+            assert_eq!(*kept, [7; 8]);
+        });
+        with_thread_local_arena(256, |arena| assert!(arena.is_empty()));
+    }
+
+    #[test]
+    fn test_shrink_to_used() {
+        let mut arena = Arena::with_capacity(4096).unwrap();
+        arena.alloc_from_iter(0..100u8).unwrap();
+        arena.shrink_to_used().unwrap();
+        assert_eq!(arena.capacity(), 100);
+        assert!(unsafe { arena.used_bytes() }.iter().copied().eq(0..100));
+        assert!(arena.is_full());
+
+        let mut arena = Arena::with_capacity_aligned(256, 8).unwrap();
+        arena.alloc(1u8).unwrap();
+        *arena.alloc_down(2u64).unwrap() += 1;
+        arena.shrink_to_used().unwrap();
+        assert_eq!(arena.capacity(), 16);
+        assert_eq!(arena.used(), 9);
+        let top = unsafe { *arena.at_offset::<u64>(8) };
+        assert_eq!(top, 3);
+    }
+
+    #[test]
+    fn test_reset_checked() {
+        let mut arena = Arena::with_capacity(64).unwrap();
+        // Only leaked blocks can still be live once the borrows are gone.
+        std::mem::forget(Box::new_in(1u32, &arena));
+        let b = arena.boxed(2u64).unwrap();
+        let mut v = arena.alloc_vec::<u8>(2).unwrap();
+        v.extend([1, 2, 3]);
+        std::mem::forget(v);
+        drop(b);
+        assert_eq!(arena.reset_checked(), Err(2));
+        assert!(!arena.is_empty());
+        arena.reset();
+        assert_eq!(arena.reset_checked(), Ok(()));
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    #[cfg(all(feature = "mmap", unix))]
+    fn test_alloc_page_aligned_slice() {
+        let page = crate::mapped::page_size();
+        assert!(page.is_power_of_two());
+        let arena = Arena::with_capacity(3 * page).unwrap();
+        arena.alloc(1u8).unwrap();
+        let slice = arena.alloc_page_aligned_slice(100).unwrap();
+        assert_eq!(slice.as_ptr() as usize % page, 0);
+        assert_eq!(slice.len(), 100);
+    }
+
+    #[test]
+    fn test_offset_overflow_fails_cleanly() {
+        // Offsets and sizes near `usize::MAX` are rejected, not wrapped.
+        assert_eq!(padding_for_parts(&[0; 16], usize::MAX - 2, 4, 1), None);
+        assert_eq!(padding_for_parts(&[0; 16], 8, isize::MAX as usize, 8), None);
+
+        let arena = Arena::with_capacity(16).unwrap();
+        arena.alloc(1u8).unwrap();
+        let huge = Layout::from_size_align(isize::MAX as usize - 8, 8).unwrap();
+        let stats = arena.stats();
+        assert!(matches!(
+            (&arena).allocate(huge),
+            Err(std::alloc::AllocError)
+        ));
+        assert_eq!(arena.stats(), stats);
+        assert_eq!(arena.position(), 1);
     }
 }
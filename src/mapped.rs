@@ -0,0 +1,216 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Arena, Storage};
+
+/// An [`Arena`] backed by an anonymous memory mapping rather than the global
+/// allocator. The system commits pages lazily on first touch, so a large
+/// capacity that is never used costs no physical memory, and the buffer
+/// starts out zeroed without a `memset`.
+///
+/// It derefs to [`Arena`], mutably too, so it can be reset and reused as a
+/// scratch buffer. Growing it would move the contents onto the heap and
+/// unmap the pages.
+#[derive(Debug)]
+pub struct MappedArena {
+    arena: Arena<'static>,
+}
+
+impl MappedArena {
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        let ptr = match capacity {
+            0 => NonNull::dangling(),
+            _ => map(capacity)?,
+        };
+        Ok(Self::from_mapping(ptr, capacity))
+    }
+
+    /// Like [`MappedArena::with_capacity`], but the buffer starts at `addr`,
+    /// so pointers into it are the same on every run, e.g. for golden-file
+    /// tests of raw pointer dumps. Fails, rather than replacing anything
+    /// already mapped there, if the mapping cannot be placed at exactly
+    /// `addr`, if `addr` is not page-aligned or if `capacity` is zero.
+    pub fn with_capacity_at(capacity: usize, addr: usize) -> Result<Self, AllocError> {
+        if capacity == 0 || !addr.is_multiple_of(page_size()) {
+            return Err(AllocError);
+        }
+        let hint = core::ptr::without_provenance_mut(addr);
+        // Without `MAP_FIXED_NOREPLACE` the address is only a hint, and older
+        // kernels ignore the flag, so the result is checked either way.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let ptr = map_with(hint, capacity, libc::MAP_FIXED_NOREPLACE)?;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let ptr = map_with(hint, capacity, 0)?;
+        if ptr.as_ptr().addr() != addr {
+            unsafe { unmap(ptr, capacity) };
+            return Err(AllocError);
+        }
+        Ok(Self::from_mapping(ptr, capacity))
+    }
+
+    fn from_mapping(ptr: NonNull<u8>, len: usize) -> Self {
+        let arena = Arena::from_storage(Storage::Mapped { ptr, len });
+        arena.zeroed.set((0, len));
+        Self { arena }
+    }
+}
+
+impl Deref for MappedArena {
+    type Target = Arena<'static>;
+
+    fn deref(&self) -> &Arena<'static> {
+        &self.arena
+    }
+}
+
+/// The mapping is owned, so handing out the arena mutably is as sound as for
+/// any other owned buffer.
+impl DerefMut for MappedArena {
+    fn deref_mut(&mut self) -> &mut Arena<'static> {
+        &mut self.arena
+    }
+}
+
+/// The system page size, queried once.
+pub(crate) fn page_size() -> usize {
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+    match PAGE_SIZE.load(Ordering::Relaxed) {
+        0 => {
+            let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+            PAGE_SIZE.store(size, Ordering::Relaxed);
+            size
+        }
+        size => size,
+    }
+}
+
+/// Maps `len` bytes of fresh, zeroed, read-write memory.
+fn map(len: usize) -> Result<NonNull<u8>, AllocError> {
+    map_with(core::ptr::null_mut(), len, 0)
+}
+
+/// [`map`] near `hint`, with extra `mmap` flags.
+fn map_with(
+    hint: *mut libc::c_void,
+    len: usize,
+    flags: libc::c_int,
+) -> Result<NonNull<u8>, AllocError> {
+    let ptr = unsafe {
+        libc::mmap(
+            hint,
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(AllocError);
+    }
+    NonNull::new(ptr.cast()).ok_or(AllocError)
+}
+
+/// Releases a mapping made by [`map`].
+///
+/// # Safety
+///
+/// `ptr` and `len` must come from one successful call to [`map`] or
+/// [`map_with`], and the memory must not be used afterwards.
+pub(crate) unsafe fn unmap(ptr: NonNull<u8>, len: usize) {
+    unsafe { libc::munmap(ptr.as_ptr().cast(), len) };
+}
+
+unsafe impl Allocator for &MappedArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&self.arena).allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&self.arena).allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (&self.arena).deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { (&self.arena).grow(ptr, old_layout, new_layout) }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { (&self.arena).shrink(ptr, old_layout, new_layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MappedArena;
+
+    #[test]
+    fn it_works() {
+        let arena = MappedArena::with_capacity(1 << 20).unwrap();
+        let mut v = Vec::new_in(&arena);
+        v.extend(0..10_000u64);
+        let b = Box::new_in(7u64, &arena);
+        assert_eq!(v.iter().sum::<u64>(), 49_995_000);
+        assert_eq!(*b, 7);
+        assert_eq!(arena.capacity(), 1 << 20);
+        assert!(arena.used() >= 80_008);
+    }
+
+    #[test]
+    // Miri only emulates private anonymous mappings, without fixed flags.
+    #[cfg_attr(miri, ignore)]
+    fn test_with_capacity_at() {
+        // Find a free, page-aligned range by mapping one and giving it back.
+        let addr = {
+            let probe = MappedArena::with_capacity(1 << 16).unwrap();
+            probe.alloc(0u8).unwrap() as *mut u8 as usize
+        };
+        let arena = MappedArena::with_capacity_at(1 << 16, addr).unwrap();
+        let first = arena.alloc(7u64).unwrap() as *mut u64;
+        assert_eq!(first as usize, addr);
+        assert!(arena.base_alignment() >= super::page_size());
+        assert_eq!(unsafe { *first }, 7);
+
+        assert!(MappedArena::with_capacity_at(1 << 16, addr).is_err());
+        assert!(MappedArena::with_capacity_at(1 << 16, addr + 1).is_err());
+        assert!(MappedArena::with_capacity_at(0, addr).is_err());
+    }
+
+    #[test]
+    fn test_reset_and_reuse() {
+        let mut arena = MappedArena::with_capacity(1 << 16).unwrap();
+        let first = arena.alloc([1u8; 1024]).unwrap() as *mut [u8; 1024];
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        let second = arena.alloc([2u8; 1024]).unwrap() as *mut [u8; 1024];
+        assert_eq!(first, second);
+        arena.push_scope();
+        arena.alloc(3u64).unwrap();
+        arena.pop_scope();
+        assert_eq!(arena.used(), 1024);
+        assert_eq!(unsafe { *second }, [2; 1024]);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let arena = MappedArena::with_capacity(0).unwrap();
+        assert!(arena.alloc(1u8).is_err());
+    }
+}
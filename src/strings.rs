@@ -0,0 +1,75 @@
+use alloc::collections::BTreeSet;
+use core::cell::RefCell;
+
+use crate::Arena;
+
+/// Packs many short strings into an [`Arena`], each returned as its own
+/// `&str` borrowed from the arena. Strings need no alignment padding, so
+/// unless the arena was built with a granularity, calling
+/// [`Arena::reserve`] with their total length checks up front that all of
+/// them fit.
+///
+/// A deduplicating interner hands out the same memory for equal strings, so
+/// they can be compared by pointer.
+#[derive(Debug)]
+pub struct StringArena<'a> {
+    arena: &'a Arena<'a>,
+    seen: Option<RefCell<BTreeSet<&'a str>>>,
+}
+
+impl<'a> StringArena<'a> {
+    /// An interner that copies every string, even repeated ones.
+    pub fn new(arena: &'a Arena<'a>) -> Self {
+        Self { arena, seen: None }
+    }
+
+    /// An interner that copies each distinct string once.
+    pub fn deduplicating(arena: &'a Arena<'a>) -> Self {
+        Self {
+            arena,
+            seen: Some(RefCell::new(BTreeSet::new())),
+        }
+    }
+
+    /// Copies `s` into the arena, or returns the earlier copy when
+    /// deduplicating. Returns `None` if the arena is full.
+    pub fn intern(&self, s: &str) -> Option<&'a str> {
+        let Some(seen) = &self.seen else {
+            return self.arena.alloc_str(s).ok().map(|s| &*s);
+        };
+        if let Some(&interned) = seen.borrow().get(s) {
+            return Some(interned);
+        }
+        let interned = &*self.arena.alloc_str(s).ok()?;
+        seen.borrow_mut().insert(interned);
+        Some(interned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, StringArena};
+
+    #[test]
+    fn test_dedup_aliases_equal_strings() {
+        let arena = Arena::with_capacity(64).unwrap();
+        let strings = StringArena::deduplicating(&arena);
+        let foo = strings.intern("foo").unwrap();
+        let bar = strings.intern("bar").unwrap();
+        let again = strings.intern("foo").unwrap();
+        assert_eq!((foo, bar), ("foo", "bar"));
+        assert!(core::ptr::eq(foo, again));
+        assert_eq!(arena.used(), 6);
+    }
+
+    #[test]
+    fn test_plain_copies_and_fills_up() {
+        let arena = Arena::with_capacity(8).unwrap();
+        let strings = StringArena::new(&arena);
+        let a = strings.intern("abc").unwrap();
+        let b = strings.intern("abc").unwrap();
+        assert!(!core::ptr::eq(a, b));
+        assert_eq!(strings.intern("abc"), None);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,208 @@
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    mem::MaybeUninit,
+    ops::Deref,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::padding_in;
+
+/// A bump allocator like [`Arena`](crate::Arena) whose offset is atomic, so
+/// `&SyncArena` can be shared between threads.
+#[derive(Debug)]
+pub struct SyncArena {
+    offset: AtomicUsize,
+    /// A leaked `Box<[MaybeUninit<u8>]>`, kept raw so blocks handed out to
+    /// other threads are not asserted over by later allocations looking at
+    /// the buffer.
+    allocation: NonNull<[u8]>,
+}
+
+// SAFETY: the buffer is owned like the box it came from, and blocks are only
+// handed out once each, through the atomic offset.
+unsafe impl Send for SyncArena {}
+unsafe impl Sync for SyncArena {}
+
+impl SyncArena {
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        let buffer = Box::into_raw(Box::<[u8]>::try_new_uninit_slice(capacity)?);
+        Ok(Self {
+            offset: AtomicUsize::new(0),
+            allocation: NonNull::new(buffer as *mut [u8]).ok_or(AllocError)?,
+        })
+    }
+
+    pub fn can_fit<T>(&self) -> bool {
+        self.padding(Layout::new::<T>()).is_some()
+    }
+    pub fn can_fit_slice<T>(&self, n: usize) -> bool {
+        Layout::new::<T>()
+            .repeat(n)
+            .ok()
+            .and_then(|(l, _)| self.padding(l))
+            .is_some()
+    }
+
+    /// Total size of the backing buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.allocation.len()
+    }
+
+    /// Bytes handed out so far, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.offset.load(Ordering::Acquire)
+    }
+
+    /// Bytes left in the arena; see [`Arena::remaining`](crate::Arena::remaining).
+    pub fn remaining(&self) -> usize {
+        self.allocation.len() - self.used()
+    }
+
+    fn padding(&self, layout: Layout) -> Option<usize> {
+        padding_in(self.allocation.as_ptr(), self.used(), layout)
+    }
+}
+
+impl Drop for SyncArena {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.allocation.as_ptr() as *mut [MaybeUninit<u8>]) });
+    }
+}
+
+unsafe impl Allocator for &SyncArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return unsafe {
+                Ok(NonNull::new_unchecked(core::ptr::slice_from_raw_parts_mut(
+                    layout.dangling_ptr().as_ptr(),
+                    layout.size(),
+                )))
+            };
+        }
+        // Retried until no other thread moved the offset between reading it
+        // and publishing the bumped value.
+        let mut padding = 0;
+        let offset = self
+            .offset
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |offset| {
+                padding = padding_in(self.allocation.as_ptr(), offset, layout)?;
+                Some(offset + padding + layout.size())
+            })
+            .map_err(|_| AllocError)?;
+        let padded_ptr = unsafe { self.allocation.cast::<u8>().add(offset + padding) };
+        Ok(NonNull::slice_from_raw_parts(padded_ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+/// An owned, cloneable handle to a [`SyncArena`], for collections that
+/// have to outlive the stack frame holding the arena. `Arc<SyncArena>`
+/// itself cannot implement [`Allocator`] outside the standard library, so
+/// this wraps one.
+///
+/// Sharing is sound because every clone points at the same arena, whose
+/// atomic offset keeps allocations from any clone disjoint, and the buffer
+/// is only freed once the last clone, and with it every collection
+/// allocated from it, is gone. A plain [`Arena`](crate::Arena) cannot offer
+/// this: its offset is not atomic, so it is not `Sync`.
+#[derive(Debug, Clone)]
+pub struct SharedArena(Arc<SyncArena>);
+
+impl SharedArena {
+    pub fn new(arena: SyncArena) -> Self {
+        Self(Arc::new(arena))
+    }
+}
+
+impl From<Arc<SyncArena>> for SharedArena {
+    fn from(arena: Arc<SyncArena>) -> Self {
+        Self(arena)
+    }
+}
+
+impl Deref for SharedArena {
+    type Target = SyncArena;
+
+    fn deref(&self) -> &SyncArena {
+        &self.0
+    }
+}
+
+unsafe impl Allocator for SharedArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&*self.0).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { (&*self.0).deallocate(ptr, layout) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SharedArena, SyncArena};
+
+    #[test]
+    fn it_works() {
+        let arena = SyncArena::with_capacity(64).unwrap();
+        // Byte arrays, so the count does not depend on the buffer's address.
+        let a = Box::new_in([5u8; 8], &arena);
+        let b = Box::new_in(7u8, &arena);
+        assert_eq!((*a, *b), ([5; 8], 7));
+        assert_eq!(arena.used(), 9);
+        assert!(arena.can_fit_slice::<u8>(55));
+        assert!(!arena.can_fit_slice::<u8>(56));
+    }
+
+    #[test]
+    fn test_concurrent_allocations_disjoint() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 100;
+        let arena = SyncArena::with_capacity(THREADS * PER_THREAD * 16).unwrap();
+        let mut ranges: Vec<(usize, usize)> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let arena = &arena;
+                    s.spawn(move || {
+                        (0..PER_THREAD)
+                            .map(|i| {
+                                let b = Box::leak(Box::new_in([t as u8, i as u8, 0, 0], arena));
+                                let start = b.as_ptr() as usize;
+                                (start, start + b.len())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        ranges.sort();
+        for pair in ranges.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "overlapping allocations");
+        }
+        assert_eq!(ranges.len(), THREADS * PER_THREAD);
+    }
+
+    #[test]
+    fn test_shared_handle() {
+        struct Log {
+            lines: Vec<u32, SharedArena>,
+        }
+        fn make(arena: &SharedArena) -> Log {
+            let mut lines = Vec::new_in(arena.clone());
+            lines.extend([1, 2, 3]);
+            Log { lines }
+        }
+        let arena = SharedArena::new(SyncArena::with_capacity(64).unwrap());
+        let log = make(&arena);
+        drop(arena);
+        let moved = std::thread::spawn(move || log).join().unwrap();
+        assert_eq!(moved.lines, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,222 @@
+use alloc::vec::{self, Vec};
+use core::{alloc::AllocError, cell::RefCell, marker::PhantomData, mem, ptr::NonNull};
+
+use crate::{Arena, ArenaError};
+
+/// An arena of `T`s that owns its values and drops all of them when it is
+/// dropped, unlike [`Arena`] which never runs destructors.
+#[derive(Debug)]
+pub struct TypedArena<T> {
+    arena: Arena<'static>,
+    values: RefCell<Vec<NonNull<T>>>,
+    marker: PhantomData<T>,
+}
+
+impl<T> TypedArena<T> {
+    /// Creates an arena with room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Result<Self, AllocError> {
+        let bytes = Arena::array_layout::<T>(capacity)
+            .ok()
+            .and_then(|layout| layout.size().checked_add(layout.align() - 1))
+            .ok_or(AllocError)?;
+        Ok(Self {
+            arena: Arena::with_capacity(bytes)?,
+            values: RefCell::new(Vec::with_capacity(capacity)),
+            marker: PhantomData,
+        })
+    }
+
+    /// Moves `value` into the arena. It is dropped together with the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn push(&self, value: T) -> Result<&mut T, ArenaError> {
+        // The reference handed out is derived from the stored pointer, so
+        // the pointer stays usable once the reference is gone.
+        let value = NonNull::from(self.arena.alloc(value)?);
+        self.values.borrow_mut().push(value);
+        Ok(unsafe { &mut *value.as_ptr() })
+    }
+
+    /// Number of values in the arena.
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every value `is_live` rejects and moves the rest down to close
+    /// the gaps, keeping their order, then hands the freed tail back to the
+    /// arena. Returns, for each old index, the value's new index, or `None`
+    /// if it was dropped.
+    ///
+    /// If `is_live` or a destructor panics, the values not yet visited are
+    /// leaked rather than dropped twice.
+    pub fn compact(&mut self, mut is_live: impl FnMut(&T) -> bool) -> Vec<Option<usize>> {
+        let mut values = mem::take(self.values.get_mut());
+        let mut remap = Vec::with_capacity(values.len());
+        let mut len = 0;
+        for i in 0..values.len() {
+            let value = values[i];
+            if is_live(unsafe { value.as_ref() }) {
+                // Values sit back to back in push order, so slot `len` is
+                // at or below `value` and already vacated.
+                unsafe { value.as_ptr().copy_to(values[len].as_ptr(), 1) };
+                self.values.get_mut().push(values[len]);
+                remap.push(Some(len));
+                len += 1;
+            } else {
+                unsafe { value.as_ptr().drop_in_place() };
+                remap.push(None);
+            }
+        }
+        if let Some(end) = values
+            .get(len)
+            .and_then(|slot| self.arena.offset_of(slot.as_ptr().cast()))
+        {
+            unsafe { self.arena.set_offset(end) };
+        }
+        values.clear();
+        remap
+    }
+
+    /// Moves the values out of the arena in the order they were pushed.
+    /// Values the iterator never yields are dropped together with it.
+    pub fn drain(mut self) -> impl Iterator<Item = T> {
+        Drain {
+            values: mem::take(self.values.get_mut()).into_iter(),
+            _arena: self,
+        }
+    }
+}
+
+struct Drain<T> {
+    values: vec::IntoIter<NonNull<T>>,
+    // Keeps the backing memory alive; its own list of values is empty.
+    _arena: TypedArena<T>,
+}
+
+impl<T> Iterator for Drain<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // SAFETY: each pointer is yielded once and removed from the list, so
+        // the value is never read or dropped again.
+        self.values
+            .next()
+            .map(|value| unsafe { value.as_ptr().read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T> Drop for Drain<T> {
+    fn drop(&mut self) {
+        for value in self.values.by_ref() {
+            unsafe { value.as_ptr().drop_in_place() };
+        }
+    }
+}
+
+// SAFETY: the recorded pointers only point at values the arena owns, so
+// moving the arena moves those values with it, which is fine when `T: Send`.
+unsafe impl<T: Send> Send for TypedArena<T> {}
+
+impl<T> Drop for TypedArena<T> {
+    fn drop(&mut self) {
+        for value in self.values.get_mut().drain(..) {
+            unsafe { value.as_ptr().drop_in_place() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::TypedArena;
+
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_drops_every_value() {
+        let drops = Rc::new(Cell::new(0));
+        let arena = TypedArena::with_capacity(10).unwrap();
+        for _ in 0..10 {
+            arena.push(Counted(drops.clone())).unwrap();
+        }
+        assert_eq!(arena.len(), 10);
+        assert!(arena.push(Counted(drops.clone())).is_err());
+        // The value that did not fit was dropped by the failed push.
+        assert_eq!(drops.get(), 1);
+        drop(arena);
+        assert_eq!(drops.get(), 11);
+    }
+
+    #[test]
+    fn test_push_returns_usable_reference() {
+        let arena = TypedArena::with_capacity(2).unwrap();
+        let a = arena.push(String::from("a")).unwrap();
+        a.push('b');
+        let c = arena.push(String::from("c")).unwrap();
+        assert_eq!((a.as_str(), c.as_str()), ("ab", "c"));
+    }
+
+    #[test]
+    fn test_compact() {
+        let drops = Rc::new(Cell::new(0));
+        let mut arena = TypedArena::with_capacity(6).unwrap();
+        for i in 0..6 {
+            arena.push((i, Counted(drops.clone()))).unwrap();
+        }
+        let remap = arena.compact(|(i, _)| i % 2 == 0);
+        assert_eq!(remap, [Some(0), None, Some(1), None, Some(2), None]);
+        assert_eq!(drops.get(), 3);
+        assert_eq!(arena.len(), 3);
+        // The freed tail is reused by later pushes.
+        for i in 6..9 {
+            arena.push((i, Counted(drops.clone()))).unwrap();
+        }
+        let ids: Vec<_> = arena.drain().map(|(i, _)| i).collect();
+        assert_eq!(ids, [0, 2, 4, 6, 7, 8]);
+        assert_eq!(drops.get(), 9);
+    }
+
+    #[test]
+    fn test_drain_moves_values_out() {
+        let drops = Rc::new(Cell::new(0));
+        let arena = TypedArena::with_capacity(5).unwrap();
+        for i in 0..5 {
+            arena.push((i, Counted(drops.clone()))).unwrap();
+        }
+        let drained: Vec<_> = arena.drain().collect();
+        assert_eq!(drops.get(), 0);
+        let ids: Vec<_> = drained.iter().map(|(i, _)| *i).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 4]);
+        drop(drained);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn test_partial_drain_drops_the_rest() {
+        let drops = Rc::new(Cell::new(0));
+        let arena = TypedArena::with_capacity(5).unwrap();
+        for _ in 0..5 {
+            arena.push(Counted(drops.clone())).unwrap();
+        }
+        let mut drain = arena.drain();
+        let first = drain.next().unwrap();
+        drop(drain);
+        assert_eq!(drops.get(), 4);
+        drop(first);
+        assert_eq!(drops.get(), 5);
+    }
+}
@@ -0,0 +1,24 @@
+//! Uses the arena from a `#![no_std]` crate through `core` and `alloc` only.
+#![no_std]
+#![feature(allocator_api)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use arena::Arena;
+
+#[test]
+fn owned_arena_without_std() {
+    let arena = Arena::with_capacity(64).unwrap();
+    let a = Box::new_in(5u32, &arena);
+    let mut v: Vec<u8, _> = Vec::new_in(&arena);
+    v.extend_from_slice(b"abc");
+    assert_eq!((*a, &v[..]), (5, &b"abc"[..]));
+}
+
+#[test]
+fn borrowed_arena_without_allocator() {
+    let mut buf = [0u8; 32];
+    let arena = Arena::from_buffer(&mut buf);
+    assert_eq!(arena.alloc_str("no heap").unwrap(), "no heap");
+}